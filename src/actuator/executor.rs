@@ -0,0 +1,114 @@
+use crate::actuator::retries::RetryLedger;
+use crate::errors::AppError;
+use crate::perceiver::bitbucket::api::BitbucketClient;
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use crate::perceiver::gitea::api::GiteaClient;
+use crate::perceiver::github::api::GitHubClient;
+use crate::perceiver::gitlab::api::GitLabClient;
+use crate::perceiver::jenkins::api::JenkinsClient;
+use crate::planner::action_plan::ActionPlan;
+use tracing::{info, warn};
+
+/// Carries out one planned action against the platform `event` came from.
+pub async fn execute(event: &NormalizedEvent, action: &ActionPlan, retries: &RetryLedger) -> Result<(), AppError> {
+    match action {
+        ActionPlan::RetryJob { job_id, max_attempts } => {
+            if !retries.admit(event, *max_attempts) {
+                info!("Not retrying {} again: already retried or out of attempts", event.platform_id);
+                return Ok(());
+            }
+            retry_job(event, job_id).await
+        }
+        ActionPlan::CommentOnPR { pr_number, message } => comment_on_pr(event, *pr_number, message).await,
+        ActionPlan::SendEmail { .. } | ActionPlan::SendSlack { .. } => {
+            warn!("Notification actions are not supported yet: {:?}", action);
+            Ok(())
+        }
+    }
+}
+
+async fn retry_job(event: &NormalizedEvent, job_id: &str) -> Result<(), AppError> {
+    // Builds are re-run from their URL, whatever repository they built.
+    if event.platform == Platform::Jenkins {
+        let Some(build_url) = event.metadata.get("build_url") else {
+            warn!("Cannot re-run Jenkins build {} without its URL", event.pipeline_id);
+            return Ok(());
+        };
+        JenkinsClient::from_env().rebuild(build_url).await?;
+        info!("Requested re-run of Jenkins build {}", event.pipeline_id);
+        return Ok(());
+    }
+
+    let repository = repository(event)?;
+
+    match event.platform {
+        Platform::GitHub => {
+            GitHubClient::for_event(event)
+                .await?
+                .rerun_job(repository, job_id)
+                .await?
+        }
+        Platform::Gitea => GiteaClient::from_env()?.rerun_job(repository, job_id).await?,
+        // Steps cannot be re-run on their own, so the whole pipeline is.
+        Platform::Bitbucket => {
+            let (Some(branch), Some(commit)) = (&event.branch, &event.commit_sha) else {
+                warn!("Cannot re-run Bitbucket pipeline {} without its branch and commit", event.pipeline_id);
+                return Ok(());
+            };
+            BitbucketClient::from_env()
+                .rerun_pipeline(repository, branch, commit)
+                .await?;
+            info!("Requested re-run of pipeline {} for step {} in {}", event.pipeline_id, job_id, repository);
+            return Ok(());
+        }
+        _ => {
+            warn!("Job retries are not supported for {:?}", event.platform);
+            return Ok(());
+        }
+    }
+
+    info!("Requested re-run of job {} in {}", job_id, repository);
+    Ok(())
+}
+
+async fn comment_on_pr(event: &NormalizedEvent, pr_number: Option<u64>, message: &str) -> Result<(), AppError> {
+    let Some(pr_number) = pr_number else {
+        info!("No pull request to comment on for {}", event.platform_id);
+        return Ok(());
+    };
+    let repository = repository(event)?;
+
+    match event.platform {
+        Platform::GitHub => {
+            GitHubClient::for_event(event)
+                .await?
+                .create_comment(repository, pr_number, message)
+                .await?
+        }
+        Platform::GitLab => {
+            GitLabClient::for_event(event)
+                .await?
+                .create_merge_request_note(repository, pr_number, message)
+                .await?
+        }
+        Platform::Bitbucket => {
+            BitbucketClient::from_env()
+                .create_comment(repository, pr_number, message)
+                .await?
+        }
+        _ => {
+            warn!("Pull request comments are not supported for {:?}", event.platform);
+            return Ok(());
+        }
+    }
+
+    info!("Commented on pull request #{} in {}", pr_number, repository);
+    Ok(())
+}
+
+fn repository(event: &NormalizedEvent) -> Result<&str, AppError> {
+    event
+        .repository
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest("Event has no repository".into()))
+}
@@ -1,14 +1,24 @@
 use crate::planner::action_plan::ActionPlan;
 use crate::perceiver::event::NormalizedEvent;
 use crate::errors::AppError;
+use crate::actuator::retries::RetryLedger;
+use tracing::error;
 
 pub mod executor;
 pub mod notifier;
+pub mod retries;
 
 pub async fn execute_actions(
     event: &NormalizedEvent,
     actions: &[ActionPlan],
+    retries: &RetryLedger,
 ) -> Result<(), AppError> {
-    // TODO: Implement actual action execution
+    // A failed action is logged rather than aborting the rest of the plan.
+    for action in actions {
+        if let Err(e) = executor::execute(event, action, retries).await {
+            error!("Failed to execute {:?} for {}: {}", action, event.platform_id, e);
+        }
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,132 @@
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CAPACITY: usize = 10_000;
+/// How long retries of the same work are remembered.
+const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Retries already requested for work whose platform does not number its
+/// attempts, such as Bitbucket pipelines and Jenkins builds, so work that
+/// fails every time is not re-run forever.
+///
+/// A re-run is a new pipeline or build, so retries are counted per commit:
+/// per pipeline on Bitbucket, which can only re-run whole pipelines, and per
+/// job elsewhere. Each pipeline or build is retried at most once, however
+/// many of its steps ask for it.
+pub struct RetryLedger {
+    retries: Mutex<HashMap<String, Retries>>,
+}
+
+struct Retries {
+    /// Pipelines or builds a retry was requested for.
+    runs: HashSet<String>,
+    expires_at: Instant,
+}
+
+impl RetryLedger {
+    pub fn new() -> Self {
+        Self {
+            retries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a retry of `event`'s work, returning `false` when it was
+    /// already retried or has reached `max_attempts`. Events that carry an
+    /// attempt number were already checked by the planner and always pass.
+    pub fn admit(&self, event: &NormalizedEvent, max_attempts: u32) -> bool {
+        if event.attempt.is_some() {
+            return true;
+        }
+
+        let (key, run) = match event.platform {
+            Platform::Bitbucket => (
+                format!(
+                    "{:?}:{}@{}",
+                    event.platform,
+                    event.repository.as_deref().unwrap_or_default(),
+                    event.commit_sha.as_deref().unwrap_or_default()
+                ),
+                event.pipeline_id.clone(),
+            ),
+            _ => (
+                format!(
+                    "{:?}:{}:{}@{}",
+                    event.platform,
+                    event.repository.as_deref().unwrap_or_default(),
+                    event.job_name.as_deref().unwrap_or_default(),
+                    event.commit_sha.as_deref().unwrap_or_default()
+                ),
+                format!("{}:{}", event.pipeline_id, event.job_id.as_deref().unwrap_or_default()),
+            ),
+        };
+
+        let now = Instant::now();
+        let mut retries = self.retries.lock().unwrap();
+        if retries.len() >= CAPACITY {
+            retries.retain(|_, retried| retried.expires_at > now);
+        }
+
+        let retried = retries.entry(key).or_insert_with(|| Retries {
+            runs: HashSet::new(),
+            expires_at: now + TTL,
+        });
+        if retried.expires_at <= now {
+            retried.runs.clear();
+        }
+        retried.expires_at = now + TTL;
+
+        // The first run is attempt 1, and each retry adds one.
+        if retried.runs.contains(&run) || retried.runs.len() as u32 + 1 >= max_attempts {
+            return false;
+        }
+        retried.runs.insert(run);
+        true
+    }
+}
+
+impl Default for RetryLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perceiver::event::EventType;
+
+    fn step(pipeline_id: &str, step: &str) -> NormalizedEvent {
+        let mut event = NormalizedEvent::new(
+            Platform::Bitbucket,
+            pipeline_id.to_string(),
+            Some(step.to_string()),
+            EventType::JobFailed,
+            None,
+        );
+        event.repository = Some("acme/widgets".to_string());
+        event.commit_sha = Some("abc123".to_string());
+        event
+    }
+
+    #[test]
+    fn retries_each_bitbucket_pipeline_once_up_to_the_limit() {
+        let ledger = RetryLedger::new();
+
+        assert!(ledger.admit(&step("1", "build"), 3));
+        assert!(!ledger.admit(&step("1", "test"), 3));
+        assert!(ledger.admit(&step("2", "build"), 3));
+        assert!(!ledger.admit(&step("3", "build"), 3));
+    }
+
+    #[test]
+    fn leaves_numbered_attempts_to_the_planner() {
+        let ledger = RetryLedger::new();
+        let mut event = step("1", "build");
+        event.attempt = Some(1);
+
+        assert!(ledger.admit(&event, 1));
+        assert!(ledger.admit(&event, 1));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::analyzer::diagnosis::Diagnosis;
+use crate::analyzer::log_parser::{Annotation, AnnotationLevel, LogSection};
+use crate::config::Config;
+use crate::perceiver::event::{EventType, NormalizedEvent};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Looks for a problem in a log, one line at a time, as the log is read.
+pub trait LineDetector: Send {
+    /// Called for every line, numbered from zero, with the innermost section
+    /// it belongs to.
+    fn line(&mut self, number: usize, line: &str, section: Option<&LogSection>);
+
+    /// Called for every annotation the log reports.
+    fn annotation(&mut self, _annotation: &Annotation) {}
+
+    /// Called as each section ends, innermost first.
+    fn section_finished(&mut self, _section: &LogSection) {}
+
+    /// What was found once the log has been read.
+    fn finish(self: Box<Self>) -> Vec<Diagnosis>;
+}
+
+/// Detectors to run over the logs of `event`; logs are not fetched when
+/// there are none.
+pub fn for_event(event: &NormalizedEvent, config: &Config) -> Vec<Box<dyn LineDetector>> {
+    let mut detectors: Vec<Box<dyn LineDetector>> = Vec::new();
+
+    if matches!(event.event_type, EventType::JobFailed | EventType::JobTimedOut) {
+        detectors.push(Box::new(FlakyTestDetector::new()));
+        detectors.push(Box::<FailedStepDetector>::default());
+    }
+
+    // A failed run's logs (on GitHub, an archive of every job's) are read for
+    // the first error they report, naming the job and step it came from.
+    if matches!(event.event_type, EventType::PipelineErrored | EventType::PipelineTimedOut) {
+        detectors.push(Box::<FailedStepDetector>::default());
+    }
+
+    if matches!(
+        event.event_type,
+        EventType::JobSucceeded | EventType::JobFailed | EventType::JobTimedOut
+    ) {
+        let limits = event
+            .job_name
+            .as_deref()
+            .and_then(|job_name| Some((job_name, config.step_durations_for(job_name)?)));
+        if let Some((job_name, limits)) = limits {
+            detectors.push(Box::new(StepRuntimeDetector {
+                job_name: job_name.to_string(),
+                limits: limits.clone(),
+                found: Vec::new(),
+            }));
+        }
+    }
+
+    detectors
+}
+
+/// Flags the first line reporting a failed test.
+pub struct FlakyTestDetector {
+    pattern: Regex,
+    found: Option<(String, String)>,
+}
+
+impl FlakyTestDetector {
+    pub fn new() -> Self {
+        Self {
+            pattern: Regex::new(r"(?i)(test|spec).*failed").unwrap(),
+            found: None,
+        }
+    }
+}
+
+impl Default for FlakyTestDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineDetector for FlakyTestDetector {
+    fn line(&mut self, _number: usize, line: &str, section: Option<&LogSection>) {
+        if self.found.is_some() || !self.pattern.is_match(line) {
+            return;
+        }
+
+        let reason = match section {
+            Some(section) => format!("Test matched failure pattern in section `{}`", section.name),
+            None => "Test matched failure pattern".to_string(),
+        };
+        self.found = Some((extract_test_name(line), reason));
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Diagnosis> {
+        self.found
+            .map(|(test_name, reason)| Diagnosis::FlakyTest { test_name, reason })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Reports the first error a failed job annotated its log with, citing the
+/// step it came from, or its section when the log does not mark steps.
+#[derive(Default)]
+pub struct FailedStepDetector {
+    found: Option<Annotation>,
+}
+
+impl LineDetector for FailedStepDetector {
+    fn line(&mut self, _number: usize, _line: &str, _section: Option<&LogSection>) {}
+
+    fn annotation(&mut self, annotation: &Annotation) {
+        if self.found.is_none() && annotation.level == AnnotationLevel::Error {
+            self.found = Some(annotation.clone());
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Diagnosis> {
+        self.found
+            .map(|annotation| Diagnosis::StepFailure {
+                location: annotation.location(),
+                step: annotation.step.or(annotation.section),
+                message: annotation.message,
+                // Filled in from the kept lines once the log has been read.
+                context: Vec::new(),
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Flags steps that ran longer than the limit configured for them.
+pub struct StepRuntimeDetector {
+    job_name: String,
+    /// Seconds each step may run, by step name.
+    limits: HashMap<String, u64>,
+    found: Vec<Diagnosis>,
+}
+
+impl LineDetector for StepRuntimeDetector {
+    fn line(&mut self, _number: usize, _line: &str, _section: Option<&LogSection>) {}
+
+    fn section_finished(&mut self, section: &LogSection) {
+        let (Some(limit), Some(duration)) = (self.limits.get(&section.name), section.duration()) else {
+            return;
+        };
+        if duration.as_secs() > *limit {
+            self.found.push(Diagnosis::LongStepRuntime {
+                job_name: self.job_name.clone(),
+                step: section.name.clone(),
+                duration: duration.as_secs(),
+            });
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<Diagnosis> {
+        self.found
+    }
+}
+
+fn extract_test_name(line: &str) -> String {
+    line.split_whitespace().next().unwrap_or("unknown_test").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perceiver::event::Platform;
+
+    fn event(event_type: EventType) -> NormalizedEvent {
+        NormalizedEvent::new(
+            Platform::GitHub,
+            "42".to_string(),
+            None,
+            event_type,
+            Some("https://api.github.com/repos/acme/widgets/actions/runs/42/logs".to_string()),
+        )
+    }
+
+    #[test]
+    fn reads_the_logs_of_failed_runs() {
+        let config = Config::default();
+
+        assert_eq!(for_event(&event(EventType::PipelineErrored), &config).len(), 1);
+        assert_eq!(for_event(&event(EventType::PipelineTimedOut), &config).len(), 1);
+        assert!(for_event(&event(EventType::PipelineCompleted), &config).is_empty());
+        assert!(for_event(&event(EventType::PipelineCancelled), &config).is_empty());
+    }
+}
@@ -2,6 +2,15 @@
 pub enum Diagnosis {
     FlakyTest { test_name: String, reason: String },
     LongRuntime { job_name: String, duration: u64 },
+    LongStepRuntime { job_name: String, step: String, duration: u64 },
+    /// The first error a failed job reported, and the step it came from with
+    /// the last lines of it that were kept.
+    StepFailure {
+        step: Option<String>,
+        message: String,
+        location: Option<String>,
+        context: Vec<String>,
+    },
     CacheMiss { step: String },
     InfraFailure,
     InefficientJobOrder { recommendation: String },
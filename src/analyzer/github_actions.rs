@@ -0,0 +1,348 @@
+use crate::analyzer::log_parser::{Annotation, AnnotationLevel, LogFormat, LogScanner};
+use crate::perceiver::github::api::JobStep;
+use chrono::{DateTime, SubsecRound, Utc};
+use std::collections::VecDeque;
+
+/// Reads GitHub Actions logs.
+///
+/// Every line starts with an RFC 3339 timestamp, which is stripped and used
+/// to place lines. A job's log does not say where each step begins, so its
+/// steps come from the jobs API: a line belongs to the step that had
+/// started by its timestamp, and steps are timed by the API rather than by
+/// their first and last lines. Each step is a section named as in the
+/// workflow, holding a section per `##[group]` opened within it.
+///
+/// Annotations are read from both the `##[error]`, `##[warning]` and
+/// `##[notice]` lines the runner writes and the raw workflow commands, e.g.
+/// `::error file=app.js,line=3,title=Lint::Missing semicolon`.
+pub struct ActionsLogFormat {
+    /// Steps not yet reached, in the order they ran.
+    steps: VecDeque<JobStep>,
+    step: Option<JobStep>,
+    /// Titles of the groups open in the current step, innermost last.
+    groups: Vec<String>,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl ActionsLogFormat {
+    /// Format for a whole job's log, split into the `steps` the jobs API
+    /// reported for it. Skipped steps wrote nothing and are left out.
+    pub fn job(steps: Vec<JobStep>) -> Self {
+        Self {
+            steps: steps
+                .into_iter()
+                .filter(|step| step.started_at.is_some() && step.conclusion.as_deref() != Some("skipped"))
+                .collect(),
+            step: None,
+            groups: Vec::new(),
+            last_timestamp: None,
+        }
+    }
+
+    /// Format for the log of a single step, as found in a run's archive.
+    pub fn step() -> Self {
+        Self::job(Vec::new())
+    }
+
+    /// Moves on to the steps that had started by `at`.
+    ///
+    /// The API times steps to the second, so a line in the second one step
+    /// ends and the next begins stays in the earlier step unless it opens a
+    /// group, as every step's log but the first and last does.
+    fn advance(&mut self, at: DateTime<Utc>, opens_group: bool, scanner: &mut LogScanner) {
+        while let Some(next) = self.steps.front() {
+            if next.started_at.is_some_and(|started| at < started) {
+                break;
+            }
+            let current_done = self
+                .step
+                .as_ref()
+                .and_then(|step| step.completed_at)
+                .is_none_or(|completed| at.trunc_subsecs(0) > completed);
+            if !opens_group && !current_done {
+                break;
+            }
+
+            self.end_step(scanner);
+            let next = self.steps.pop_front().unwrap();
+            scanner.start_step(next.name.clone(), next.started_at);
+            self.step = Some(next);
+        }
+    }
+
+    fn end_step(&mut self, scanner: &mut LogScanner) {
+        self.groups.clear();
+        if let Some(step) = self.step.take() {
+            scanner.end_section(&step.name, step.completed_at);
+        }
+    }
+}
+
+impl LogFormat for ActionsLogFormat {
+    fn line(&mut self, raw: &str, scanner: &mut LogScanner) {
+        let raw = raw.strip_prefix('\u{feff}').unwrap_or(raw);
+        let (timestamp, text) = split_timestamp(raw);
+        let group = text.strip_prefix("##[group]").or_else(|| text.strip_prefix("::group::"));
+
+        if let Some(timestamp) = timestamp {
+            self.advance(timestamp, group.is_some(), scanner);
+            self.last_timestamp = Some(timestamp);
+        }
+        let at = timestamp.or(self.last_timestamp);
+
+        if let Some(title) = group {
+            scanner.start_section(title.to_string(), Some(title.to_string()), at);
+            self.groups.push(title.to_string());
+            scanner.line(title);
+            return;
+        }
+        if text == "##[endgroup]" || text == "::endgroup::" {
+            if let Some(title) = self.groups.pop() {
+                scanner.end_section(&title, at);
+            }
+            return;
+        }
+
+        if let Some(annotation) = parse_annotation(text) {
+            scanner.line(&annotation.message);
+            scanner.annotate(annotation);
+            return;
+        }
+
+        let text = ["##[command]", "##[debug]", "##[section]"]
+            .iter()
+            .find_map(|prefix| text.strip_prefix(prefix))
+            .unwrap_or(text);
+        scanner.line(text);
+    }
+
+    fn finish(&mut self, scanner: &mut LogScanner) {
+        while let Some(title) = self.groups.pop() {
+            scanner.end_section(&title, self.last_timestamp);
+        }
+        // Steps that wrote nothing after the last line still ran.
+        while let Some(step) = self.steps.pop_front() {
+            self.end_step(scanner);
+            scanner.start_step(step.name.clone(), step.started_at);
+            self.step = Some(step);
+        }
+        self.end_step(scanner);
+    }
+}
+
+/// Splits the timestamp the runner prefixes each line with from its text.
+fn split_timestamp(line: &str) -> (Option<DateTime<Utc>>, &str) {
+    if let Some((prefix, text)) = line.split_once(' ') {
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(prefix) {
+            return (Some(timestamp.with_timezone(&Utc)), text);
+        }
+    }
+    (None, line)
+}
+
+fn parse_annotation(text: &str) -> Option<Annotation> {
+    let levels = [
+        ("error", AnnotationLevel::Error),
+        ("warning", AnnotationLevel::Warning),
+        ("notice", AnnotationLevel::Notice),
+    ];
+
+    for (name, level) in levels {
+        if let Some(message) = text.strip_prefix(&format!("##[{}]", name)) {
+            return Some(annotation(level, message.to_string()));
+        }
+
+        // ::<level>[ <key>=<value>,...]::<message>
+        let Some(rest) = text.strip_prefix(&format!("::{}", name)) else {
+            continue;
+        };
+        let (properties, message) = rest.split_once("::")?;
+        if !properties.is_empty() && !properties.starts_with(' ') {
+            continue;
+        }
+
+        let mut annotation = annotation(level, unescape_data(message));
+        for property in properties.trim_start().split(',').filter(|property| !property.is_empty()) {
+            let Some((key, value)) = property.split_once('=') else {
+                continue;
+            };
+            let value = unescape_property(value);
+            match key {
+                "file" => annotation.file = Some(value),
+                "line" => annotation.line = value.parse().ok(),
+                "title" => annotation.title = Some(value),
+                _ => {}
+            }
+        }
+        return Some(annotation);
+    }
+
+    None
+}
+
+fn annotation(level: AnnotationLevel, message: String) -> Annotation {
+    Annotation {
+        level,
+        message,
+        title: None,
+        file: None,
+        line: None,
+        section: None,
+        step: None,
+    }
+}
+
+/// Reverses the escaping the toolkit applies to workflow command messages.
+fn unescape_data(value: &str) -> String {
+    value.replace("%0D", "\r").replace("%0A", "\n").replace("%25", "%")
+}
+
+/// Reverses the escaping the toolkit applies to workflow command properties.
+fn unescape_property(value: &str) -> String {
+    unescape_data(&value.replace("%3A", ":").replace("%2C", ","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::detectors::{FailedStepDetector, LineDetector};
+    use crate::analyzer::diagnosis::Diagnosis;
+    use crate::analyzer::log_parser::{LogLimits, LogReport};
+
+    fn scan(format: &mut ActionsLogFormat, log: &str) -> LogReport {
+        let limits = LogLimits {
+            max_bytes: 1 << 20,
+            max_lines: 1_000,
+            head_lines: 10,
+            tail_lines: 10,
+            max_archive_bytes: 1 << 20,
+        };
+        let mut scanner = LogScanner::new(limits, Vec::new());
+        for line in log.lines() {
+            format.line(line, &mut scanner);
+        }
+        format.finish(&mut scanner);
+        scanner.finish()
+    }
+
+    fn step(number: u32, name: &str, started_at: &str, completed_at: &str) -> JobStep {
+        JobStep {
+            name: name.to_string(),
+            number,
+            conclusion: Some("success".to_string()),
+            started_at: Some(started_at.parse().unwrap()),
+            completed_at: Some(completed_at.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn splits_timestamps_from_text() {
+        let (timestamp, text) = split_timestamp("2024-05-01T10:00:02.5000000Z ##[group]Run cargo test");
+        assert_eq!(timestamp, Some("2024-05-01T10:00:02.5Z".parse().unwrap()));
+        assert_eq!(text, "##[group]Run cargo test");
+
+        assert_eq!(split_timestamp("no timestamp here"), (None, "no timestamp here"));
+        assert_eq!(split_timestamp(""), (None, ""));
+    }
+
+    #[test]
+    fn parses_runner_annotations() {
+        let annotation = parse_annotation("##[error]Process completed with exit code 1.").unwrap();
+        assert_eq!(annotation.level, AnnotationLevel::Error);
+        assert_eq!(annotation.message, "Process completed with exit code 1.");
+        assert_eq!(annotation.location(), None);
+
+        assert_eq!(parse_annotation("##[warning]Deprecated").unwrap().level, AnnotationLevel::Warning);
+        assert!(parse_annotation("##[group]Run tests").is_none());
+    }
+
+    #[test]
+    fn parses_workflow_commands_with_properties() {
+        let annotation = parse_annotation("::error file=src/app.js,line=3,title=Lint%3A semi::Missing semicolon").unwrap();
+        assert_eq!(annotation.level, AnnotationLevel::Error);
+        assert_eq!(annotation.message, "Missing semicolon");
+        assert_eq!(annotation.title.as_deref(), Some("Lint: semi"));
+        assert_eq!(annotation.location().as_deref(), Some("src/app.js:3"));
+
+        let annotation = parse_annotation("::notice::Deployed").unwrap();
+        assert_eq!(annotation.level, AnnotationLevel::Notice);
+        assert_eq!(annotation.message, "Deployed");
+
+        // Other commands sharing a prefix are not annotations.
+        assert!(parse_annotation("::errors::nope").is_none());
+        assert!(parse_annotation("::error").is_none());
+    }
+
+    #[test]
+    fn unescapes_messages_and_properties() {
+        assert_eq!(unescape_data("50%25 done%0D%0Anext line"), "50% done\r\nnext line");
+        // `%25` is unescaped last, so escaped escapes stay literal.
+        assert_eq!(unescape_data("%250A"), "%0A");
+        assert_eq!(unescape_property("a%3Ab%2Cc%0A"), "a:b,c\n");
+    }
+
+    #[test]
+    fn splits_job_log_into_api_steps() {
+        let steps = vec![
+            step(1, "Set up job", "2024-05-01T10:00:00Z", "2024-05-01T10:00:02Z"),
+            step(2, "Tests", "2024-05-01T10:00:02Z", "2024-05-01T10:01:40Z"),
+            step(3, "Complete job", "2024-05-01T10:01:40Z", "2024-05-01T10:01:41Z"),
+        ];
+        let log = "\
+2024-05-01T10:00:00.1Z Current runner version: '2.316.0'
+2024-05-01T10:00:00.2Z ##[group]Operating System
+2024-05-01T10:00:00.3Z Ubuntu
+2024-05-01T10:00:00.4Z ##[endgroup]
+2024-05-01T10:00:02.1Z Download action repository 'actions/checkout@v4'
+2024-05-01T10:00:02.6Z ##[group]Run cargo test
+2024-05-01T10:00:02.7Z cargo test
+2024-05-01T10:00:02.8Z ##[endgroup]
+2024-05-01T10:00:03.0Z ::group::Unit tests
+2024-05-01T10:01:00.0Z ::error file=src/lib.rs,line=7::assertion failed
+2024-05-01T10:01:00.1Z ::endgroup::
+2024-05-01T10:01:39.0Z test result: ok
+2024-05-01T10:01:41.2Z Cleaning up orphan processes";
+
+        let report = scan(&mut ActionsLogFormat::job(steps), log);
+        let sections: Vec<_> = report
+            .sections
+            .iter()
+            .map(|section| (section.name.as_str(), section.lines.clone(), section.duration().map(|d| d.as_secs())))
+            .collect();
+
+        assert_eq!(
+            sections,
+            vec![
+                ("Set up job", 0..4, Some(2)),
+                ("Operating System", 1..3, Some(0)),
+                ("Tests", 4..9, Some(98)),
+                ("Run cargo test", 4..6, Some(0)),
+                ("Unit tests", 6..8, Some(57)),
+                ("Complete job", 9..10, Some(1)),
+            ]
+        );
+        assert_eq!(report.annotations[0].section.as_deref(), Some("Unit tests"));
+        assert_eq!(report.annotations[0].step.as_deref(), Some("Tests"));
+
+        // Failures are reported against the step the jobs API lists.
+        let mut detector = Box::<FailedStepDetector>::default();
+        detector.annotation(&report.annotations[0]);
+        assert!(matches!(
+            detector.finish().as_slice(),
+            [Diagnosis::StepFailure { step: Some(step), .. }] if step == "Tests"
+        ));
+    }
+
+    #[test]
+    fn steps_after_the_last_line_are_still_reported() {
+        let steps = vec![
+            step(1, "Build", "2024-05-01T10:00:00Z", "2024-05-01T10:00:05Z"),
+            step(2, "Upload", "2024-05-01T10:00:05Z", "2024-05-01T10:00:30Z"),
+        ];
+        let report = scan(&mut ActionsLogFormat::job(steps), "2024-05-01T10:00:01Z building");
+
+        let names: Vec<_> = report.sections.iter().map(|section| section.name.as_str()).collect();
+        assert_eq!(names, vec!["Build", "Upload"]);
+        assert_eq!(report.sections[1].duration().map(|d| d.as_secs()), Some(25));
+    }
+}
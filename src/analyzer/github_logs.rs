@@ -0,0 +1,206 @@
+use crate::analyzer::github_actions::ActionsLogFormat;
+use crate::analyzer::log_parser::{self, FetchedLog, LineSplitter, LogBody, LogFormat, LogReport, LogScanner};
+use crate::errors::AppError;
+use crate::perceiver::event::NormalizedEvent;
+use crate::perceiver::github::api::GitHubClient;
+use reqwest::Response;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use tracing::warn;
+use zip::ZipArchive;
+
+/// Streams GitHub Actions logs from a job's or run's `logs` endpoint through
+/// `scanner`.
+///
+/// Job logs are text in the format `ActionsLogFormat` reads, split into the
+/// steps the jobs API lists for the job. Run logs are a
+/// zip archive holding a `<n>_<job>.txt` file per job and a
+/// `<job>/<n>_<step>.txt` file per step, which is scanned as one section per
+/// job containing one per step.
+pub async fn scan(event: &NormalizedEvent, logs_uri: &str, scanner: LogScanner) -> Result<FetchedLog, AppError> {
+    let client = GitHubClient::for_event(event).await?;
+
+    let Some(response) = client.download_logs(logs_uri).await? else {
+        return Ok(FetchedLog::Expired);
+    };
+
+    let report = if logs_uri.contains("/actions/runs/") {
+        scan_archive(response, scanner).await?
+    } else {
+        let steps = match (&event.repository, &event.job_id) {
+            (Some(repository), Some(job_id)) => client.job_steps(repository, job_id).await.unwrap_or_else(|e| {
+                warn!("Failed to list the steps of job {}: {}", job_id, e);
+                Vec::new()
+            }),
+            _ => Vec::new(),
+        };
+        log_parser::scan(LogBody::Response(response), ActionsLogFormat::job(steps), scanner).await?
+    };
+
+    Ok(FetchedLog::Available(report))
+}
+
+#[derive(Default)]
+struct ArchivedJob {
+    /// Position of the job's own log file in the run.
+    number: Option<u32>,
+    log: Option<usize>,
+    steps: Vec<(Option<u32>, String, usize)>,
+}
+
+/// Scans a run's log archive, preferring per-step logs over a job's combined
+/// log when both are present.
+///
+/// A zip archive can only be read once whole, so it is downloaded in full,
+/// but only up to the archive size limit; larger archives are skipped. It
+/// is unpacked on the blocking thread pool.
+async fn scan_archive(mut response: Response, mut scanner: LogScanner) -> Result<LogReport, AppError> {
+    let max_bytes = scanner.limits().max_archive_bytes;
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > max_bytes {
+            warn!("Log archive exceeds {} bytes; skipping it", max_bytes);
+            scanner.truncate();
+            return Ok(scanner.finish());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    tokio::task::spawn_blocking(move || unpack_archive(bytes, scanner))
+        .await
+        .map_err(|e| AppError::Internal(e.into()))?
+}
+
+fn unpack_archive(bytes: Vec<u8>, mut scanner: LogScanner) -> Result<LogReport, AppError> {
+    let invalid = |e: zip::result::ZipError| AppError::BadRequest(format!("Invalid log archive: {}", e));
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(invalid)?;
+
+    let mut jobs: BTreeMap<String, ArchivedJob> = BTreeMap::new();
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(invalid)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file.name().to_string();
+
+        match path.split_once('/') {
+            Some((job, step)) => {
+                let (number, step) = numbered(step);
+                jobs.entry(job.to_string()).or_default().steps.push((number, step.to_string(), index));
+            }
+            None => {
+                let (number, job) = numbered(&path);
+                let job = jobs.entry(job.to_string()).or_default();
+                job.number = number;
+                job.log = Some(index);
+            }
+        }
+    }
+
+    let mut jobs: Vec<_> = jobs.into_iter().collect();
+    jobs.sort_by_key(|(_, job)| job.number.unwrap_or(u32::MAX));
+
+    for (name, mut job) in jobs {
+        scanner.start_section(name.clone(), None, None);
+
+        if job.steps.is_empty() {
+            if let Some(index) = job.log {
+                scan_entry(&mut archive, index, &mut scanner)?;
+            }
+        }
+        job.steps.sort_by_key(|(number, _, _)| number.unwrap_or(u32::MAX));
+        for (_, step, index) in job.steps {
+            let section = format!("{}/{}", name, step);
+            scanner.start_step(section.clone(), None);
+            scan_entry(&mut archive, index, &mut scanner)?;
+            scanner.end_section(&section, None);
+        }
+
+        scanner.end_section(&name, None);
+    }
+
+    Ok(scanner.finish())
+}
+
+/// Decompresses one archive entry through `scanner`, stopping at its limits.
+fn scan_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, index: usize, scanner: &mut LogScanner) -> Result<(), AppError> {
+    let mut file = archive
+        .by_index(index)
+        .map_err(|e| AppError::BadRequest(format!("Invalid log archive: {}", e)))?;
+    let mut format = ActionsLogFormat::step();
+    let mut splitter = LineSplitter::default();
+    let mut buffer = [0; 8192];
+
+    while !scanner.is_full() {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        splitter.push(&buffer[..read], |line| format.line(line, scanner));
+    }
+    splitter.finish(|line| format.line(line, scanner));
+    format.finish(scanner);
+
+    Ok(())
+}
+
+/// Splits an archive entry name like `2_Run tests.txt` into its number and
+/// name.
+fn numbered(name: &str) -> (Option<u32>, &str) {
+    let name = name.strip_suffix(".txt").unwrap_or(name);
+    match name.split_once('_') {
+        Some((number, rest)) => match number.parse() {
+            Ok(number) => (Some(number), rest),
+            Err(_) => (None, name),
+        },
+        None => (None, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::detectors::FailedStepDetector;
+    use crate::analyzer::diagnosis::Diagnosis;
+    use crate::analyzer::log_parser::LogLimits;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn scans_each_step_of_each_job() {
+        let bytes = archive(&[
+            ("2_lint.txt", "2024-05-01T10:00:00.0000000Z linting\n"),
+            ("1_build.txt", "2024-05-01T10:00:00.0000000Z combined log, not read\n"),
+            ("build/2_Run tests.txt", "2024-05-01T10:00:02.0000000Z ##[error]boom\n"),
+            ("build/1_Set up job.txt", "2024-05-01T10:00:01.0000000Z Starting\n"),
+        ]);
+        let limits = LogLimits {
+            max_bytes: 1 << 20,
+            max_lines: 1_000,
+            head_lines: 10,
+            tail_lines: 10,
+            max_archive_bytes: 1 << 20,
+        };
+        let scanner = LogScanner::new(limits, vec![Box::<FailedStepDetector>::default()]);
+
+        let report = unpack_archive(bytes, scanner).unwrap();
+
+        let sections: Vec<_> = report.sections.iter().map(|section| section.name.as_str()).collect();
+        assert_eq!(sections, ["build", "build/Set up job", "build/Run tests", "lint"]);
+        assert_eq!(report.head, ["Starting", "boom", "linting"]);
+        assert!(matches!(
+            report.diagnoses.as_slice(),
+            [Diagnosis::StepFailure { step: Some(step), message, .. }]
+                if step == "build/Run tests" && message == "boom"
+        ));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::analyzer::log_parser::{LogFormat, LogScanner};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Reads GitLab job traces.
+///
+/// Traces are written for a terminal: ANSI escape codes are stripped, and a
+/// `\r` within a line keeps only what was written after it. Collapsible
+/// sections are delimited by `section_start:<unix time>:<name>[options]` and
+/// `section_end:<unix time>:<name>` markers, which are removed from the text;
+/// the text following a `section_start` becomes the section's header. Markers
+/// may follow output that did not end its line, which is read as a line of
+/// its own, inside the section being ended.
+pub struct TraceFormat;
+
+impl LogFormat for TraceFormat {
+    fn line(&mut self, raw: &str, scanner: &mut LogScanner) {
+        let line = ansi().replace_all(raw, "");
+        if !marker().is_match(&line) {
+            scanner.line(visible(&line));
+            return;
+        }
+
+        let mut started = None;
+        let mut last = 0;
+
+        for captures in marker().captures_iter(&line) {
+            let marker = captures.get(0).unwrap();
+            text(&line[last..marker.start()], started.take(), scanner);
+            last = marker.end();
+
+            let at = captures[2].parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0));
+            if &captures[1] == "start" {
+                started = Some((captures[3].to_string(), at));
+            } else {
+                scanner.end_section(&captures[3], at);
+            }
+        }
+        text(&line[last..], started, scanner);
+    }
+}
+
+/// Reads the text between two markers, as the header of the section started
+/// just before it, if any. Text with nothing visible is dropped.
+fn text(text: &str, started: Option<(String, Option<DateTime<Utc>>)>, scanner: &mut LogScanner) {
+    let text = visible(text);
+    let header = (!text.trim().is_empty()).then(|| text.to_string());
+
+    if let Some((name, at)) = started {
+        scanner.start_section(name, header.clone(), at);
+    }
+    if header.is_some() {
+        scanner.line(text);
+    }
+}
+
+/// What a terminal would show of `text`: only what followed its last `\r`.
+fn visible(text: &str) -> &str {
+    text.trim_end_matches('\r').rsplit('\r').next().unwrap_or_default()
+}
+
+fn ansi() -> &'static Regex {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap())
+}
+
+fn marker() -> &'static Regex {
+    static MARKER: OnceLock<Regex> = OnceLock::new();
+    MARKER.get_or_init(|| {
+        Regex::new(r"section_(start|end):(\d+):([A-Za-z0-9_.\-]+)(?:\[[^\]]*\])?\r?").unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::log_parser::{LogLimits, LogReport};
+
+    fn scan(trace: &str) -> LogReport {
+        let limits = LogLimits {
+            max_bytes: 1 << 20,
+            max_lines: 1_000,
+            head_lines: 100,
+            tail_lines: 0,
+            max_archive_bytes: 1 << 20,
+        };
+        let mut scanner = LogScanner::new(limits, Vec::new());
+        let mut format = TraceFormat;
+        for line in trace.lines() {
+            format.line(line, &mut scanner);
+        }
+        format.finish(&mut scanner);
+        scanner.finish()
+    }
+
+    /// Name, header, lines and duration in seconds of a section.
+    type Summary<'a> = (&'a str, Option<&'a str>, std::ops::Range<usize>, Option<u64>);
+
+    fn sections(report: &LogReport) -> Vec<Summary<'_>> {
+        report
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    section.name.as_str(),
+                    section.header.as_deref(),
+                    section.lines.clone(),
+                    section.duration().map(|duration| duration.as_secs()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_nested_sections() {
+        let report = scan(concat!(
+            "section_start:100:build\r\x1b[0K\x1b[36;1mBuilding\x1b[0;m\n",
+            "compiling\n",
+            "section_start:110:tests[collapsed=true]\r\x1b[0KTesting\n",
+            "test a ... ok\n",
+            "section_end:120:tests\r\x1b[0K\n",
+            "section_end:130:build\r\x1b[0K\n",
+            "done\n",
+        ));
+
+        assert_eq!(
+            sections(&report),
+            vec![
+                ("build", Some("Building"), 0..4, Some(30)),
+                ("tests", Some("Testing"), 2..4, Some(10)),
+            ]
+        );
+        assert_eq!(report.head, ["Building", "compiling", "Testing", "test a ... ok", "done"]);
+    }
+
+    #[test]
+    fn closes_unterminated_sections() {
+        let report = scan(concat!(
+            "section_start:100:build\r\x1b[0K\n",
+            "section_start:105:inner\r\x1b[0K\n",
+            "compiling\n",
+            "section_end:130:build\r\x1b[0K\n",
+            "section_start:140:deploy\r\x1b[0K\n",
+            "deploying\n",
+        ));
+
+        assert_eq!(
+            sections(&report),
+            vec![
+                ("build", None, 0..1, Some(30)),
+                ("inner", None, 0..1, None),
+                ("deploy", None, 1..2, None),
+            ]
+        );
+        assert_eq!(report.head, ["compiling", "deploying"]);
+    }
+
+    #[test]
+    fn reads_markers_in_the_middle_of_a_line() {
+        let report = scan(concat!(
+            "section_start:100:build\r\x1b[0KBuilding\n",
+            "no newlinesection_end:110:build\r\x1b[0Ksection_start:110:test\r\x1b[0KTesting\n",
+            "section_end:120:test\r\x1b[0K\n",
+        ));
+
+        assert_eq!(
+            sections(&report),
+            vec![
+                ("build", Some("Building"), 0..2, Some(10)),
+                ("test", Some("Testing"), 2..3, Some(10)),
+            ]
+        );
+        assert_eq!(report.head, ["Building", "no newline", "Testing"]);
+    }
+
+    #[test]
+    fn keeps_what_follows_a_carriage_return() {
+        let report = scan(concat!(
+            "Downloading 10%\rDownloading 50%\rDownloading 100%\n",
+            "\x1b[32;1mJob succeeded\x1b[0;m\r\n",
+            "\n",
+        ));
+
+        assert!(report.sections.is_empty());
+        assert_eq!(report.head, ["Downloading 100%", "Job succeeded", ""]);
+    }
+}
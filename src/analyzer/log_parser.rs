@@ -1,24 +1,445 @@
+use crate::analyzer::detectors::LineDetector;
+use crate::analyzer::diagnosis::Diagnosis;
+use crate::analyzer::{github_logs, gitlab_trace};
+use crate::config::instance::{PlatformInstance, PlatformInstances};
 use crate::errors::AppError;
-use reqwest::Client;
+use crate::perceiver::bitbucket;
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use crate::perceiver::github::api::installation_id;
+use crate::perceiver::gitlab::api::{GitLabClient, TraceReader};
+use crate::utils::env::env_parse;
+use crate::utils::http::is_under;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder, Response};
+use std::collections::VecDeque;
 use std::env;
+use std::ops::Range;
 
-pub async fn parse_logs(logs_uri: &str) -> Result<Vec<String>, AppError> {
-    let client = Client::new();
+const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+const DEFAULT_MAX_LINES: usize = 1_000_000;
+const DEFAULT_HEAD_LINES: usize = 50;
+const DEFAULT_TAIL_LINES: usize = 200;
+const DEFAULT_MAX_ARCHIVE_BYTES: usize = 128 * 1024 * 1024;
+/// Kept lines quoted with a failure.
+const CONTEXT_LINES: usize = 20;
+/// Longer lines are cut short, so one runaway line cannot exhaust memory.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+/// Annotations kept in a report; detectors still see every one.
+const MAX_ANNOTATIONS: usize = 100;
 
-    // Determine if GitHub or GitLab based on URL
-    let is_github = logs_uri.contains("github.com");
+/// How much of a log is analyzed and kept for context.
+#[derive(Debug, Clone, Copy)]
+pub struct LogLimits {
+    /// Bytes of log text read before analysis stops.
+    pub max_bytes: usize,
+    /// Lines read before analysis stops.
+    pub max_lines: usize,
+    /// Lines kept from the start of the log.
+    pub head_lines: usize,
+    /// Lines kept from the end of what was read.
+    pub tail_lines: usize,
+    /// Compressed bytes of a log archive downloaded before it is skipped.
+    pub max_archive_bytes: usize,
+}
+
+impl LogLimits {
+    pub fn from_env() -> Result<Self, AppError> {
+        Ok(Self {
+            max_bytes: env_parse("LOG_MAX_BYTES", DEFAULT_MAX_BYTES)?,
+            max_lines: env_parse("LOG_MAX_LINES", DEFAULT_MAX_LINES)?,
+            head_lines: env_parse("LOG_HEAD_LINES", DEFAULT_HEAD_LINES)?,
+            tail_lines: env_parse("LOG_TAIL_LINES", DEFAULT_TAIL_LINES)?,
+            max_archive_bytes: env_parse("LOG_MAX_ARCHIVE_BYTES", DEFAULT_MAX_ARCHIVE_BYTES)?,
+        })
+    }
+}
+
+/// Outcome of fetching a job's log.
+#[derive(Debug)]
+pub enum FetchedLog {
+    Available(LogReport),
+    /// The platform no longer keeps the log, e.g. GitHub after its retention
+    /// period.
+    Expired,
+}
+
+#[derive(Debug)]
+pub struct LogSection {
+    pub name: String,
+    /// Text shown on the section's collapsed line, if any.
+    pub header: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Numbers of the lines in the section, counted from zero; sections
+    /// left open run to the end of what was read.
+    pub lines: Range<usize>,
+    /// Whether the section is one of the job's steps, rather than a group
+    /// of lines the job marked within one.
+    pub step: bool,
+}
+
+impl LogSection {
+    /// Time between the section's first and last timestamps, when the log
+    /// carried both.
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        (self.finished_at? - self.started_at?).to_std().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Error,
+}
+
+/// A notice, warning or error a job reported about its own run, such as
+/// GitHub's `::error file=app.js,line=3::Missing semicolon`.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub level: AnnotationLevel,
+    pub message: String,
+    pub title: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    /// Innermost section it was reported in.
+    pub section: Option<String>,
+    /// Innermost step it was reported in, when the log marks steps.
+    pub step: Option<String>,
+}
+
+impl Annotation {
+    /// `file:line`, or just the file, when the annotation names one.
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?;
+        Some(match self.line {
+            Some(line) => format!("{}:{}", file, line),
+            None => file.clone(),
+        })
+    }
+}
+
+/// What a single pass over a log found.
+#[derive(Debug, Default)]
+pub struct LogReport {
+    pub diagnoses: Vec<Diagnosis>,
+    pub sections: Vec<LogSection>,
+    /// The first annotations reported, in order.
+    pub annotations: Vec<Annotation>,
+    /// First lines of the log.
+    pub head: Vec<String>,
+    /// Last lines read, not overlapping `head`.
+    pub tail: Vec<String>,
+    pub lines: usize,
+    pub bytes: usize,
+    /// Whether reading stopped at a limit before the end of the log.
+    pub truncated: bool,
+}
+
+impl LogReport {
+    /// What the detectors found, with each failure quoting the kept lines of
+    /// the section it was reported in, or of the whole log.
+    pub fn into_diagnoses(mut self) -> Vec<Diagnosis> {
+        let mut diagnoses = std::mem::take(&mut self.diagnoses);
+        for diagnosis in &mut diagnoses {
+            if let Diagnosis::StepFailure { step, context, .. } = diagnosis {
+                let lines = step
+                    .as_ref()
+                    .and_then(|step| self.sections.iter().rev().find(|section| &section.name == step))
+                    .map_or(0..self.lines, |section| section.lines.clone());
+                *context = self.excerpt(lines, CONTEXT_LINES);
+            }
+        }
+        diagnoses
+    }
+
+    /// The last `max` of the kept lines numbered within `lines`.
+    pub fn excerpt(&self, lines: Range<usize>, max: usize) -> Vec<String> {
+        let tail_start = self.lines - self.tail.len();
+        let kept = self
+            .head
+            .iter()
+            .enumerate()
+            .chain(self.tail.iter().enumerate().map(|(index, line)| (tail_start + index, line)));
+
+        let mut excerpt: Vec<String> = kept
+            .filter(|(number, _)| lines.contains(number))
+            .map(|(_, line)| line.clone())
+            .collect();
+        excerpt.drain(..excerpt.len().saturating_sub(max));
+        excerpt
+    }
+}
 
-    let mut req = client.get(logs_uri);
+/// Feeds each line of a log through every detector as it is read, keeping
+/// only section boundaries and the head and tail of the log.
+pub struct LogScanner {
+    limits: LogLimits,
+    detectors: Vec<Box<dyn LineDetector>>,
+    sections: Vec<LogSection>,
+    /// Indices into `sections` of the sections still open, innermost last.
+    open: Vec<usize>,
+    annotations: Vec<Annotation>,
+    head: Vec<String>,
+    tail: VecDeque<String>,
+    lines: usize,
+    bytes: usize,
+    truncated: bool,
+}
 
-    if is_github {
-        if let Ok(token) = env::var("GITHUB_TOKEN") {
-            req = req.bearer_auth(token);
+impl LogScanner {
+    pub fn new(limits: LogLimits, detectors: Vec<Box<dyn LineDetector>>) -> Self {
+        Self {
+            limits,
+            detectors,
+            sections: Vec::new(),
+            open: Vec::new(),
+            annotations: Vec::new(),
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            lines: 0,
+            bytes: 0,
+            truncated: false,
         }
     }
 
-    let response = req.send().await.map_err(|e| {
-        AppError::Http(anyhow::anyhow!("Failed to fetch logs: {}", e).into())
-    })?;
+    pub fn limits(&self) -> LogLimits {
+        self.limits
+    }
+
+    /// Whether a limit was reached, after which further input is ignored.
+    pub fn is_full(&self) -> bool {
+        self.truncated
+    }
+
+    /// Stops the scan early, as when a log cannot be read within the limits.
+    pub fn truncate(&mut self) {
+        self.truncated = true;
+    }
+
+    pub fn line(&mut self, line: &str) {
+        if self.truncated {
+            return;
+        }
+        if self.lines >= self.limits.max_lines || self.bytes + line.len() > self.limits.max_bytes {
+            self.truncated = true;
+            return;
+        }
+
+        let number = self.lines;
+        self.lines += 1;
+        self.bytes += line.len() + 1;
+
+        let section = self.open.last().map(|&index| &self.sections[index]);
+        for detector in &mut self.detectors {
+            detector.line(number, line, section);
+        }
+
+        if self.head.len() < self.limits.head_lines {
+            self.head.push(line.to_string());
+        } else if self.limits.tail_lines > 0 {
+            if self.tail.len() == self.limits.tail_lines {
+                self.tail.pop_front();
+            }
+            self.tail.push_back(line.to_string());
+        }
+    }
+
+    /// Records an annotation in the innermost open section and step.
+    pub fn annotate(&mut self, mut annotation: Annotation) {
+        if self.truncated {
+            return;
+        }
+        annotation.section = self.open.last().map(|&index| self.sections[index].name.clone());
+        annotation.step = self
+            .open
+            .iter()
+            .rev()
+            .map(|&index| &self.sections[index])
+            .find(|section| section.step)
+            .map(|section| section.name.clone());
+
+        for detector in &mut self.detectors {
+            detector.annotation(&annotation);
+        }
+        if self.annotations.len() < MAX_ANNOTATIONS {
+            self.annotations.push(annotation);
+        }
+    }
+
+    pub fn start_section(&mut self, name: String, header: Option<String>, started_at: Option<DateTime<Utc>>) {
+        self.open_section(name, header, started_at, false);
+    }
+
+    /// Starts a section for one of the job's steps; sections started inside
+    /// it are groups within the step.
+    pub fn start_step(&mut self, name: String, started_at: Option<DateTime<Utc>>) {
+        self.open_section(name, None, started_at, true);
+    }
+
+    fn open_section(&mut self, name: String, header: Option<String>, started_at: Option<DateTime<Utc>>, step: bool) {
+        if self.truncated {
+            return;
+        }
+        self.open.push(self.sections.len());
+        self.sections.push(LogSection {
+            name,
+            header,
+            started_at,
+            finished_at: None,
+            lines: self.lines..self.lines,
+            step,
+        });
+    }
+
+    /// Ends the innermost open section called `name`, and any left open
+    /// inside it.
+    pub fn end_section(&mut self, name: &str, finished_at: Option<DateTime<Utc>>) {
+        if self.truncated {
+            return;
+        }
+        let Some(position) = self.open.iter().rposition(|&index| self.sections[index].name == name) else {
+            return;
+        };
+        self.sections[self.open[position]].finished_at = finished_at;
+        self.close_sections(position);
+    }
+
+    /// Closes the open sections from `position` inward, innermost first.
+    fn close_sections(&mut self, position: usize) {
+        for index in self.open.drain(position..).rev() {
+            let section = &mut self.sections[index];
+            section.lines.end = self.lines;
+            for detector in &mut self.detectors {
+                detector.section_finished(section);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> LogReport {
+        self.close_sections(0);
+
+        LogReport {
+            diagnoses: self.detectors.into_iter().flat_map(|detector| detector.finish()).collect(),
+            sections: self.sections,
+            annotations: self.annotations,
+            head: self.head,
+            tail: self.tail.into(),
+            lines: self.lines,
+            bytes: self.bytes,
+            truncated: self.truncated,
+        }
+    }
+}
+
+/// Turns a platform's raw log lines into scanner input.
+pub trait LogFormat {
+    fn line(&mut self, raw: &str, scanner: &mut LogScanner);
+
+    /// Called once the last line has been read.
+    fn finish(&mut self, _scanner: &mut LogScanner) {}
+}
+
+/// Logs without markup: every line is passed through as is.
+pub struct PlainText;
+
+impl LogFormat for PlainText {
+    fn line(&mut self, raw: &str, scanner: &mut LogScanner) {
+        scanner.line(raw);
+    }
+}
+
+/// Splits a byte stream into lines, holding at most `MAX_LINE_BYTES` of an
+/// unfinished line.
+#[derive(Default)]
+pub struct LineSplitter {
+    partial: Vec<u8>,
+}
+
+impl LineSplitter {
+    pub fn push(&mut self, chunk: &[u8], mut emit: impl FnMut(&str)) {
+        let mut rest = chunk;
+        while let Some(end) = rest.iter().position(|&byte| byte == b'\n') {
+            self.append(&rest[..end]);
+            self.flush(&mut emit);
+            rest = &rest[end + 1..];
+        }
+        self.append(rest);
+    }
+
+    /// Emits the last line when the stream did not end with a newline.
+    pub fn finish(&mut self, mut emit: impl FnMut(&str)) {
+        if !self.partial.is_empty() {
+            self.flush(&mut emit);
+        }
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        let room = MAX_LINE_BYTES.saturating_sub(self.partial.len());
+        self.partial.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    fn flush(&mut self, emit: &mut impl FnMut(&str)) {
+        let line = String::from_utf8_lossy(&self.partial);
+        emit(line.strip_suffix('\r').unwrap_or(&line));
+        self.partial.clear();
+    }
+}
+
+/// A log being downloaded.
+pub enum LogBody {
+    Response(Response),
+    GitLabTrace(TraceReader),
+}
+
+impl LogBody {
+    async fn chunk(&mut self) -> Result<Option<Bytes>, AppError> {
+        match self {
+            LogBody::Response(response) => Ok(response.chunk().await?),
+            LogBody::GitLabTrace(trace) => trace.chunk().await,
+        }
+    }
+}
+
+/// Reads `body` through `scanner` until it ends or a limit is reached; the
+/// rest of the body is never downloaded.
+pub async fn scan(mut body: LogBody, mut format: impl LogFormat, mut scanner: LogScanner) -> Result<LogReport, AppError> {
+    let mut splitter = LineSplitter::default();
+
+    while !scanner.is_full() {
+        let Some(chunk) = body.chunk().await? else {
+            break;
+        };
+        splitter.push(&chunk, |line| format.line(line, &mut scanner));
+    }
+    splitter.finish(|line| format.line(line, &mut scanner));
+    format.finish(&mut scanner);
+
+    Ok(scanner.finish())
+}
+
+/// Streams the log of `event` from `logs_uri` through `scanner`.
+pub async fn scan_logs(event: &NormalizedEvent, logs_uri: &str, scanner: LogScanner) -> Result<FetchedLog, AppError> {
+    match event.platform {
+        Platform::GitHub => return github_logs::scan(event, logs_uri, scanner).await,
+        // GitLab's job page is HTML; the trace itself comes from the API.
+        Platform::GitLab => {
+            if let (Some(project), Some(job_id)) = (&event.repository, &event.job_id) {
+                let trace = GitLabClient::for_event(event).await?.job_trace(project, job_id)?;
+                let report = scan(LogBody::GitLabTrace(trace), gitlab_trace::TraceFormat, scanner).await?;
+                return Ok(FetchedLog::Available(report));
+            }
+        }
+        _ => {}
+    }
+
+    let instance = PlatformInstances::global()?.for_url(logs_uri);
+    let client = instance.map(|i| i.http().clone()).unwrap_or_else(Client::new);
+
+    let req = authorize(client.get(logs_uri), event, instance, logs_uri).await?;
+
+    let response = req.send().await?;
 
     if !response.status().is_success() {
         return Err(AppError::BadRequest(format!(
@@ -27,9 +448,40 @@ pub async fn parse_logs(logs_uri: &str) -> Result<Vec<String>, AppError> {
         )));
     }
 
-    let text = response.text().await.map_err(|e| {
-        AppError::Http(anyhow::anyhow!("Failed to read logs: {}", e).into())
-    })?;
+    let report = scan(LogBody::Response(response), PlainText, scanner).await?;
+
+    Ok(FetchedLog::Available(report))
+}
+
+/// Attaches the credentials for the platform serving the logs, based on URL.
+async fn authorize(
+    req: RequestBuilder,
+    event: &NormalizedEvent,
+    instance: Option<&PlatformInstance>,
+    logs_uri: &str,
+) -> Result<RequestBuilder, AppError> {
+    let token = if let Some(instance) = instance {
+        // A configured GitHub or GitLab host.
+        match &event.repository {
+            Some(repository) => instance.token_for(repository, installation_id(event)).await?,
+            None => None,
+        }
+    } else if is_under(logs_uri, &bitbucket::api::api_url()) {
+        env::var("BITBUCKET_TOKEN").ok()
+    } else if env::var("GITEA_BASE_URL").is_ok_and(|base| is_under(logs_uri, &base)) {
+        env::var("GITEA_TOKEN").ok()
+    } else if env::var("JENKINS_URL").is_ok_and(|base| is_under(logs_uri, &base)) {
+        // Jenkins authenticates API calls with a user name and API token.
+        return Ok(match (env::var("JENKINS_USER"), env::var("JENKINS_API_TOKEN")) {
+            (Ok(user), Ok(token)) => req.basic_auth(user, Some(token)),
+            _ => req,
+        });
+    } else {
+        None
+    };
 
-    Ok(text.lines().map(str::to_string).collect())
+    Ok(match token {
+        Some(token) => req.bearer_auth(token),
+        None => req,
+    })
 }
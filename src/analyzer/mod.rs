@@ -1,8 +1,13 @@
 use crate::perceiver::event::{EventType, NormalizedEvent};
 use crate::errors::AppError;
-use regex::Regex;
+use log_parser::{FetchedLog, LogLimits, LogScanner};
+use tracing::info;
 
+pub mod detectors;
 pub mod diagnosis;
+pub mod github_actions;
+pub mod github_logs;
+pub mod gitlab_trace;
 pub mod log_parser;
 
 pub async fn analyze_event(
@@ -11,15 +16,26 @@ pub async fn analyze_event(
 ) -> Result<Vec<diagnosis::Diagnosis>, AppError> {
     let mut diagnoses = Vec::new();
 
-    if let Some(logs_uri) = &event.logs_uri {
-        let log_lines = log_parser::parse_logs(logs_uri).await?;
+    if let Some(diagnosis) = detect_long_runtime(event, config) {
+        diagnoses.push(diagnosis);
+    }
 
-        if let EventType::JobFailed = event.event_type {
-            if let Some((test_name, reason)) = detect_flaky_tests(&log_lines) {
-                diagnoses.push(diagnosis::Diagnosis::FlakyTest {
-                    test_name,
-                    reason,
-                });
+    let detectors = detectors::for_event(event, config);
+    if let (Some(logs_uri), false) = (&event.logs_uri, detectors.is_empty()) {
+        let scanner = LogScanner::new(LogLimits::from_env()?, detectors);
+
+        match log_parser::scan_logs(event, logs_uri, scanner).await? {
+            FetchedLog::Available(report) => {
+                if report.truncated {
+                    info!(
+                        "Analyzed only the first {} lines ({} bytes) of the logs for {}",
+                        report.lines, report.bytes, event.platform_id
+                    );
+                }
+                diagnoses.extend(report.into_diagnoses());
+            }
+            FetchedLog::Expired => {
+                info!("Logs for {} have expired; skipping log analysis", event.platform_id);
             }
         }
     }
@@ -27,21 +43,21 @@ pub async fn analyze_event(
     Ok(diagnoses)
 }
 
-fn detect_flaky_tests(log_lines: &[String]) -> Option<(String, String)> {
-    let test_fail_pattern = Regex::new(r"(test|spec).*failed").ok()?;
-
-    for line in log_lines {
-        if test_fail_pattern.is_match(&line.to_lowercase()) {
-            return Some((
-                extract_test_name(line),
-                "Test matched failure pattern".to_string(),
-            ));
-        }
+/// Flags a finished job that ran longer than its configured limit.
+fn detect_long_runtime(
+    event: &NormalizedEvent,
+    config: &crate::config::Config,
+) -> Option<diagnosis::Diagnosis> {
+    if !matches!(
+        event.event_type,
+        EventType::JobSucceeded | EventType::JobFailed | EventType::JobTimedOut
+    ) {
+        return None;
     }
 
-    None
-}
+    let job_name = event.job_name.clone().or_else(|| event.job_id.clone())?;
+    let duration = event.duration()?.as_secs();
 
-fn extract_test_name(line: &str) -> String {
-    line.split_whitespace().next().unwrap_or("unknown_test").to_string()
+    (duration > config.max_duration_for(&job_name))
+        .then_some(diagnosis::Diagnosis::LongRuntime { job_name, duration })
 }
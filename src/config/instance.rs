@@ -0,0 +1,300 @@
+use crate::errors::AppError;
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use crate::perceiver::github::auth::GitHubAuth;
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, Client};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const GITHUB_COM_URL: &str = "https://github.com";
+const GITHUB_COM_API_URL: &str = "https://api.github.com";
+const GITLAB_COM_URL: &str = "https://gitlab.com";
+
+/// Settings for one GitHub or GitLab host, as written in
+/// `PLATFORM_INSTANCES_FILE`:
+///
+/// ```yaml
+/// instances:
+///   - platform: gitlab
+///     web_url: https://gitlab.corp.example
+///     ca_bundle: /etc/ssl/corp-ca.pem
+///     token_env: CORP_GITLAB_TOKEN
+///   - platform: github
+///     web_url: https://ghe.corp.example
+///     app_id: "12"
+///     app_private_key_path: /etc/optimizer/ghe-app.pem
+/// ```
+///
+/// `api_url` defaults to `<web_url>/api/v4` for GitLab and
+/// `<web_url>/api/v3` for GitHub Enterprise Server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstanceConfig {
+    pub platform: InstancePlatform,
+    pub web_url: String,
+    pub api_url: Option<String>,
+    /// PEM bundle of extra root certificates for a private CA.
+    pub ca_bundle: Option<PathBuf>,
+    pub token: Option<String>,
+    /// Environment variable to read the token from, keeping it out of the file.
+    pub token_env: Option<String>,
+    /// GitHub App credentials, used instead of `token` when set.
+    pub app_id: Option<String>,
+    /// PEM contents of the app's private key, or a path to it.
+    pub app_private_key: Option<String>,
+    pub app_private_key_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstancePlatform {
+    GitHub,
+    GitLab,
+}
+
+impl InstancePlatform {
+    fn matches(&self, platform: &Platform) -> bool {
+        matches!(
+            (self, platform),
+            (InstancePlatform::GitHub, Platform::GitHub) | (InstancePlatform::GitLab, Platform::GitLab)
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct InstancesFile {
+    instances: Vec<InstanceConfig>,
+}
+
+/// A configured GitHub or GitLab host with its HTTP client and credentials.
+pub struct PlatformInstance {
+    platform: InstancePlatform,
+    web_url: String,
+    api_url: String,
+    http: Client,
+    http_without_redirects: Client,
+    token: Option<String>,
+    github_auth: Option<GitHubAuth>,
+}
+
+impl PlatformInstance {
+    pub fn new(config: InstanceConfig) -> Result<Self, AppError> {
+        let web_url = config.web_url.trim_end_matches('/').to_string();
+        let api_url = match &config.api_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => default_api_url(config.platform, &web_url),
+        };
+
+        let certificates = match &config.ca_bundle {
+            Some(path) => read_ca_bundle(path)?,
+            None => Vec::new(),
+        };
+        let client = |redirects: Policy| {
+            certificates
+                .iter()
+                .fold(Client::builder().redirect(redirects), |builder, certificate| {
+                    builder.add_root_certificate(certificate.clone())
+                })
+                .build()
+        };
+        let http = client(Policy::default())?;
+        let http_without_redirects = client(Policy::none())?;
+
+        let token = match (&config.token, &config.token_env) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(name)) => Some(
+                std::env::var(name)
+                    .map_err(|_| AppError::ConfigError(format!("Missing {} for {}", name, web_url)))?,
+            ),
+            (None, None) => None,
+        };
+
+        let github_auth = match config.platform {
+            InstancePlatform::GitHub => {
+                let app = match &config.app_id {
+                    Some(app_id) => {
+                        let pem = match (&config.app_private_key, &config.app_private_key_path) {
+                            (Some(pem), _) => pem.clone().into_bytes(),
+                            (None, Some(path)) => std::fs::read(path)?,
+                            (None, None) => {
+                                return Err(AppError::ConfigError(format!(
+                                    "GitHub App for {} has no private key",
+                                    web_url
+                                )))
+                            }
+                        };
+                        Some((app_id.clone(), GitHubAuth::app_key(&pem)?))
+                    }
+                    None => None,
+                };
+                Some(GitHubAuth::new(http.clone(), &api_url, app, token.clone()))
+            }
+            InstancePlatform::GitLab => None,
+        };
+
+        Ok(Self {
+            platform: config.platform,
+            web_url,
+            api_url,
+            http,
+            http_without_redirects,
+            token,
+            github_auth,
+        })
+    }
+
+    pub fn web_url(&self) -> &str {
+        &self.web_url
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Client trusting the instance's CA bundle, if one is configured.
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    /// Like [`Self::http`], but returning redirects to the caller, for
+    /// endpoints that answer with a signed URL which must be fetched without
+    /// the instance's credentials.
+    pub fn http_without_redirects(&self) -> &Client {
+        &self.http_without_redirects
+    }
+
+    /// Token for calls against `repository`. GitHub instances mint app
+    /// installation tokens when an app is configured; otherwise this is the
+    /// instance's static token.
+    pub async fn token_for(
+        &self,
+        repository: &str,
+        installation_id: Option<u64>,
+    ) -> Result<Option<String>, AppError> {
+        match &self.github_auth {
+            Some(auth) => auth.token_for(repository, installation_id).await,
+            None => Ok(self.token.clone()),
+        }
+    }
+
+    /// Whether `url` points at this instance's web UI or API.
+    fn serves(&self, url: &str) -> bool {
+        let under = |base: &str| {
+            url.strip_prefix(base)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+        under(&self.api_url) || under(&self.web_url)
+    }
+}
+
+/// Every configured GitHub and GitLab host.
+///
+/// Hosts listed in `PLATFORM_INSTANCES_FILE` take precedence; GitHub.com and
+/// GitLab.com (or whatever `GITHUB_URL` / `GITLAB_URL` point at) are always
+/// available as the default for their platform, configured from the
+/// platform's environment variables.
+pub struct PlatformInstances {
+    instances: Vec<PlatformInstance>,
+}
+
+impl PlatformInstances {
+    pub fn from_env() -> Result<Self, AppError> {
+        let mut configs = match std::env::var("PLATFORM_INSTANCES_FILE") {
+            Ok(path) => {
+                let contents = std::fs::read(&path)?;
+                let file: InstancesFile = serde_yaml::from_slice(&contents).map_err(|e| {
+                    AppError::ConfigError(format!("Invalid platform instances file {}: {}", path, e))
+                })?;
+                file.instances
+            }
+            Err(_) => Vec::new(),
+        };
+
+        configs.push(InstanceConfig {
+            platform: InstancePlatform::GitHub,
+            web_url: std::env::var("GITHUB_URL").unwrap_or_else(|_| GITHUB_COM_URL.to_string()),
+            api_url: std::env::var("GITHUB_API_URL").ok(),
+            ca_bundle: std::env::var("GITHUB_CA_BUNDLE").ok().map(PathBuf::from),
+            token: std::env::var("GITHUB_TOKEN").ok(),
+            token_env: None,
+            app_id: std::env::var("GITHUB_APP_ID").ok(),
+            app_private_key: std::env::var("GITHUB_APP_PRIVATE_KEY").ok(),
+            app_private_key_path: std::env::var("GITHUB_APP_PRIVATE_KEY_PATH").ok().map(PathBuf::from),
+        });
+        configs.push(InstanceConfig {
+            platform: InstancePlatform::GitLab,
+            web_url: std::env::var("GITLAB_URL").unwrap_or_else(|_| GITLAB_COM_URL.to_string()),
+            api_url: std::env::var("GITLAB_API_URL").ok(),
+            ca_bundle: std::env::var("GITLAB_CA_BUNDLE").ok().map(PathBuf::from),
+            token: std::env::var("GITLAB_TOKEN").ok(),
+            token_env: None,
+            app_id: None,
+            app_private_key: None,
+            app_private_key_path: None,
+        });
+
+        let instances = configs
+            .into_iter()
+            .map(PlatformInstance::new)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { instances })
+    }
+
+    /// Process-wide instances, built from the environment on first use so
+    /// every caller shares one set of clients and token caches.
+    pub fn global() -> Result<&'static PlatformInstances, AppError> {
+        static INSTANCES: OnceLock<PlatformInstances> = OnceLock::new();
+
+        if let Some(instances) = INSTANCES.get() {
+            return Ok(instances);
+        }
+        let instances = Self::from_env()?;
+        Ok(INSTANCES.get_or_init(|| instances))
+    }
+
+    /// Instance for `platform` whose web URL is `instance_url`, or the
+    /// platform default when the instance is unknown.
+    ///
+    /// An `instance_url` that matches no configured instance is an error
+    /// rather than falling back to the default, which would send that host's
+    /// requests, and the default's credentials, to the wrong place.
+    pub fn resolve(&self, platform: &Platform, instance_url: Option<&str>) -> Result<&PlatformInstance, AppError> {
+        let candidates = || self.instances.iter().filter(|i| i.platform.matches(platform));
+
+        if let Some(url) = instance_url.map(|u| u.trim_end_matches('/')) {
+            return candidates()
+                .find(|i| i.web_url.eq_ignore_ascii_case(url))
+                .ok_or_else(|| AppError::ConfigError(format!("No {:?} instance configured for {}", platform, url)));
+        }
+
+        // Defaults were pushed after the configured instances.
+        candidates()
+            .last()
+            .ok_or_else(|| AppError::ConfigError(format!("No instance configured for {:?}", platform)))
+    }
+
+    /// Instance the event was delivered from.
+    pub fn for_event(&self, event: &NormalizedEvent) -> Result<&PlatformInstance, AppError> {
+        self.resolve(&event.platform, event.instance_url.as_deref())
+    }
+
+    /// Instance serving `url`, if any.
+    pub fn for_url(&self, url: &str) -> Option<&PlatformInstance> {
+        self.instances.iter().find(|i| i.serves(url))
+    }
+}
+
+fn default_api_url(platform: InstancePlatform, web_url: &str) -> String {
+    match platform {
+        InstancePlatform::GitHub if web_url.eq_ignore_ascii_case(GITHUB_COM_URL) => GITHUB_COM_API_URL.to_string(),
+        InstancePlatform::GitHub => format!("{}/api/v3", web_url),
+        InstancePlatform::GitLab => format!("{}/api/v4", web_url),
+    }
+}
+
+fn read_ca_bundle(path: &Path) -> Result<Vec<Certificate>, AppError> {
+    let pem = std::fs::read(path)?;
+    Certificate::from_pem_bundle(&pem)
+        .map_err(|e| AppError::ConfigError(format!("Invalid CA bundle {:?}: {}", path, e)))
+}
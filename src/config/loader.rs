@@ -8,77 +8,70 @@ pub async fn load_for_event(event: &NormalizedEvent) -> Result<Config, AppError>
     match event.platform {
         Platform::GitHub => load_from_github(event).await,
         Platform::GitLab => load_from_gitlab(event).await,
+        Platform::Bitbucket => load_from_bitbucket(event).await,
+        Platform::Gitea => load_from_gitea(event).await,
+        // Jenkins has no repository API to read `.optimizer.yml` from.
+        Platform::Jenkins => Ok(Config::default()),
     }
 }
 
 async fn load_from_github(event: &NormalizedEvent) -> Result<Config, AppError> {
-    use octocrab::Octocrab;
+    use crate::perceiver::github::api::GitHubClient;
 
-    let repo = event
-        .metadata
-        .get("repository")
-        .ok_or_else(|| AppError::BadRequest("Missing repository metadata".into()))?;
+    let (repo, sha) = repository_and_commit(event)?;
 
-    let sha = event
-        .metadata
-        .get("commit_sha")
-        .ok_or_else(|| AppError::BadRequest("Missing commit_sha metadata".into()))?;
+    let client = GitHubClient::for_event(event).await?;
 
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() != 2 {
-        return Err(AppError::BadRequest("Invalid repository format".into()));
+    for path in [".optimizer.yml", ".optimizer.json"] {
+        if let Ok(Some(content)) = client.get_file(repo, sha, path).await {
+            return parse_config_bytes(&content, path);
+        }
     }
 
-    let (owner, repo_name) = (parts[0], parts[1]);
-    let octo = Octocrab::builder().build()?;
+    Ok(Config::default())
+}
+
+async fn load_from_gitlab(event: &NormalizedEvent) -> Result<Config, AppError> {
+    use crate::perceiver::gitlab::api::GitLabClient;
+
+    let (project, sha) = repository_and_commit(event)?;
+
+    let client = GitLabClient::for_event(event).await?;
 
     for path in [".optimizer.yml", ".optimizer.json"] {
-        let result = octo
-            .repos(owner, repo_name)
-            .get_content()
-            .path(path)
-            .r#ref(sha)
-            .send()
-            .await;
-
-        if let Ok(response) = result {
-            if let Some(file) = response.items.into_iter().next() {
-                let decoded = base64::decode(file.content.replace('\n', ""))
-                    .map_err(|e| AppError::ConfigError(e.to_string()))?;
-                return parse_config_bytes(&decoded, path);
-            }
+        if let Ok(Some(content)) = client.get_file(project, sha, path).await {
+            return parse_config_bytes(&content, path);
         }
     }
 
     Ok(Config::default())
 }
 
-async fn load_from_gitlab(event: &NormalizedEvent) -> Result<Config, AppError> {
-    use gitlab::Gitlab;
+async fn load_from_bitbucket(event: &NormalizedEvent) -> Result<Config, AppError> {
+    use crate::perceiver::bitbucket::api::BitbucketClient;
+
+    let (repo, sha) = repository_and_commit(event)?;
 
-    let project = event
-        .metadata
-        .get("project")
-        .ok_or_else(|| AppError::BadRequest("Missing GitLab project metadata".into()))?;
+    let client = BitbucketClient::from_env();
 
-    let sha = event
-        .metadata
-        .get("commit_sha")
-        .ok_or_else(|| AppError::BadRequest("Missing commit_sha metadata".into()))?;
+    for path in [".optimizer.yml", ".optimizer.json"] {
+        if let Ok(Some(content)) = client.get_file(repo, sha, path).await {
+            return parse_config_bytes(&content, path);
+        }
+    }
+
+    Ok(Config::default())
+}
+
+async fn load_from_gitea(event: &NormalizedEvent) -> Result<Config, AppError> {
+    use crate::perceiver::gitea::api::GiteaClient;
+
+    let (repo, sha) = repository_and_commit(event)?;
 
-    let token = std::env::var("GITLAB_TOKEN")
-        .map_err(|_| AppError::ConfigError("Missing GITLAB_TOKEN".into()))?;
-    let client = Gitlab::new("gitlab.com", token)?;
+    let client = GiteaClient::from_env()?;
 
     for path in [".optimizer.yml", ".optimizer.json"] {
-        let file = client
-            .projects()
-            .repository(project)
-            .get_file(path, sha);
-
-        if let Ok(file_info) = file {
-            let content = base64::decode(file_info.content.replace('\n', ""))
-                .map_err(|e| AppError::ConfigError(e.to_string()))?;
+        if let Ok(Some(content)) = client.get_file(repo, sha, path).await {
             return parse_config_bytes(&content, path);
         }
     }
@@ -86,6 +79,17 @@ async fn load_from_gitlab(event: &NormalizedEvent) -> Result<Config, AppError> {
     Ok(Config::default())
 }
 
+/// Repository and commit the event ran for, which `.optimizer.yml` is read at.
+fn repository_and_commit(event: &NormalizedEvent) -> Result<(&str, &str), AppError> {
+    match (&event.repository, &event.commit_sha) {
+        (Some(repository), Some(sha)) => Ok((repository, sha)),
+        _ => Err(AppError::BadRequest(format!(
+            "Event {} has no repository or commit to load configuration from",
+            event.platform_id
+        ))),
+    }
+}
+
 fn parse_config_bytes(bytes: &[u8], path: &str) -> Result<Config, AppError> {
     if path.ends_with(".yml") {
         serde_yaml::from_slice(bytes).map_err(|e| AppError::ConfigError(e.to_string()))
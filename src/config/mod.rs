@@ -1,20 +1,66 @@
 // src/config/mod.rs
+pub mod instance;
 pub mod loader;  // This exposes the loader submodule
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
+/// Repository settings read from `.optimizer.yml`:
+///
+/// ```yaml
+/// max_job_duration: 1800
+/// max_retry_attempts: 2
+/// jobs:
+///   integration-tests:
+///     max_duration: 5400
+///     steps:
+///       Run tests: 3600
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub allow_flaky_retry: bool,
+    /// Attempts a job may reach through automatic retries; a job on this
+    /// attempt or later is not retried again.
+    pub max_retry_attempts: u32,
+    /// Seconds a job may run before it is reported as long-running.
     pub max_job_duration: u64,
+    /// Per-job overrides, keyed by job name.
+    pub jobs: HashMap<String, JobConfig>,
     // Add other config fields
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobConfig {
+    pub max_duration: Option<u64>,
+    /// Seconds each step may run, by its name in the workflow; steps not
+    /// listed are not checked.
+    #[serde(default)]
+    pub steps: HashMap<String, u64>,
+}
+
+impl Config {
+    /// Runtime limit in seconds for `job_name`.
+    pub fn max_duration_for(&self, job_name: &str) -> u64 {
+        self.jobs
+            .get(job_name)
+            .and_then(|job| job.max_duration)
+            .unwrap_or(self.max_job_duration)
+    }
+
+    /// Runtime limits in seconds for the steps of `job_name`, by step name.
+    pub fn step_durations_for(&self, job_name: &str) -> Option<&HashMap<String, u64>> {
+        self.jobs.get(job_name).map(|job| &job.steps).filter(|steps| !steps.is_empty())
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             allow_flaky_retry: true,
+            max_retry_attempts: 3,
             max_job_duration: 3600,
+            jobs: HashMap::new(),
         }
     }
 }
\ No newline at end of file
@@ -19,11 +19,6 @@ pub enum AppError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("Channel error: {0}")]
-    Channel(
-        #[from] tokio::sync::mpsc::error::SendError<crate::perceiver::event::NormalizedEvent>,
-    ),
-
     #[error("Hyper HTTP error: {0}")]
     Http(#[from] hyper::Error),
 
@@ -32,22 +27,88 @@ pub enum AppError {
 
     #[error("Request error: {0}")]
     RequestError(String), // ✅ OPTIONAL fallback if you want plain string handling
+
+    #[error("Event queue saturated: {limit} limit reached")]
+    Saturated {
+        limit: SaturatedLimit,
+        retry_after: std::time::Duration,
+    },
+}
+
+/// Which admission limit turned a delivery away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SaturatedLimit {
+    Queue,
+    Source,
+    Repository,
+}
+
+impl SaturatedLimit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SaturatedLimit::Queue => "queue",
+            SaturatedLimit::Source => "source",
+            SaturatedLimit::Repository => "repository",
+        }
+    }
+}
+
+impl std::fmt::Display for SaturatedLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
+        if let AppError::Saturated { retry_after, .. } = &self {
+            let retry_after = retry_after.as_secs().max(1).to_string();
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(axum::http::header::RETRY_AFTER, retry_after)],
+                self.to_string(),
+            )
+                .into_response();
+        }
+
         let status = match self {
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
             AppError::ConfigError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::Channel(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Http(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::HttpClient(_) => StatusCode::BAD_GATEWAY,
             AppError::RequestError(_) => StatusCode::BAD_REQUEST,
+            AppError::Saturated { .. } => StatusCode::SERVICE_UNAVAILABLE,
         };
 
         (status, self.to_string()).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturated_responses_ask_senders_to_retry_later() {
+        let response = AppError::Saturated {
+            limit: SaturatedLimit::Repository,
+            retry_after: std::time::Duration::from_secs(30),
+        }
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "30");
+
+        // Sub-second delays are rounded up rather than sent as zero.
+        let response = AppError::Saturated {
+            limit: SaturatedLimit::Queue,
+            retry_after: std::time::Duration::from_millis(200),
+        }
+        .into_response();
+        assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "1");
+    }
+}
+
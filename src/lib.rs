@@ -4,4 +4,5 @@ pub mod analyzer;
 pub mod runner;
 pub mod config;
 pub mod planner;
-pub mod actuator;
\ No newline at end of file
+pub mod actuator;
+pub mod utils;
@@ -1,42 +1,152 @@
 use axum::{
-    extract::Request,
+    extract::{Path, Query, Request, State},
     http::{HeaderMap, StatusCode},
-    routing::post,
+    routing::{get, post},
+    Json,
     Router,
     response::{IntoResponse, Response},
-    body::{Body, to_bytes},
+    body::to_bytes,
 };
 
-use tokio::sync::mpsc;
-use tracing_subscriber;
+use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 use ci_cd_optimizer::errors::AppError;
-use ci_cd_optimizer::perceiver::webhook::WebhookProcessor;
+use ci_cd_optimizer::perceiver::archive::{DeliveryArchive, ListQuery};
+use ci_cd_optimizer::perceiver::client::{Poller, PollerConfig};
+use ci_cd_optimizer::perceiver::dedup::DeliveryDeduplicator;
+use ci_cd_optimizer::perceiver::event::NormalizedEvent;
+use ci_cd_optimizer::perceiver::filter::EventFilter;
+use ci_cd_optimizer::perceiver::metrics::DeliveryMetrics;
+use ci_cd_optimizer::perceiver::queue::EventQueue;
+use ci_cd_optimizer::perceiver::review::ReviewResolver;
+use ci_cd_optimizer::perceiver::secrets::SecretResolver;
+use ci_cd_optimizer::perceiver::webhook::{job_key, WebhookProcessor, WebhookSource};
 use ci_cd_optimizer::runner::agent::Agent;
+use ci_cd_optimizer::runner::replay;
+
+#[derive(Parser)]
+#[command(about = "CI/CD pipeline optimizer agent")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the webhook server and agent (default)
+    Serve,
+    /// Replay recorded webhook deliveries and print diagnoses and planned actions
+    Replay {
+        /// NDJSON file or directory of `.json` / `.ndjson` delivery files
+        path: PathBuf,
+        /// Skip webhook signature and token checks
+        #[arg(long)]
+        no_verify: bool,
+    },
+}
+
+#[derive(Clone)]
+struct AppState {
+    queue: Arc<EventQueue>,
+    processor: Arc<WebhookProcessor>,
+    metrics: Arc<DeliveryMetrics>,
+    filter: Arc<EventFilter>,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Validate environment
-    validate_secrets()?;
+    let cli = Cli::parse();
 
     tracing_subscriber::fmt::init();
 
-    // Create event channel
-    let (tx, rx) = mpsc::channel(100);
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        Command::Replay { path, no_verify } => run_replay(&path, no_verify).await,
+    }
+}
+
+async fn run_replay(path: &std::path::Path, no_verify: bool) -> anyhow::Result<()> {
+    let deliveries = replay::load_deliveries(path)?;
+
+    // Replays use an in-memory seen-set so captured redeliveries still dedupe
+    // without touching the server's persisted state.
+    let dedup = DeliveryDeduplicator::new(deliveries.len().max(1), std::time::Duration::from_secs(3600));
+    let mut processor = WebhookProcessor::new(dedup).with_secrets(Arc::new(SecretResolver::from_env()?));
+    if no_verify {
+        processor = processor.without_verification();
+    }
+
+    replay::replay(&processor, &deliveries).await;
+
+    Ok(())
+}
+
+async fn serve() -> anyhow::Result<()> {
+    // Validate environment
+    let secrets = Arc::new(SecretResolver::from_env()?);
+    if !secrets.is_configured() {
+        validate_secrets()?;
+    }
+
+    // Pick up rotated webhook secrets without a restart
+    if secrets.is_configured() {
+        let resolver = secrets.clone();
+        tokio::spawn(async move {
+            if let Err(e) = SecretResolver::watch(resolver).await {
+                tracing::error!("Webhook secret reloading stopped: {}", e);
+            }
+        });
+    }
+
+    let mut processor = WebhookProcessor::new(DeliveryDeduplicator::from_env()?).with_secrets(secrets);
+    if let Some(archive) = DeliveryArchive::from_env()? {
+        processor = processor.with_archive(archive);
+    }
+    let processor = Arc::new(processor);
+    let archive_enabled = processor.archive().is_some();
 
-    // Clone tx for webhook handlers
-    let github_tx = tx.clone();
-    let gitlab_tx = tx;
+    // Events are persisted here before webhooks are acknowledged
+    let queue = Arc::new(EventQueue::from_env()?);
+    let filter = Arc::new(EventFilter::from_env()?);
+
+    // Start poller for repositories that cannot deliver webhooks
+    let poller_config = PollerConfig::from_env()?;
+    if poller_config.is_enabled() {
+        let poller = Poller::new(poller_config, queue.clone(), filter.clone())?;
+        tokio::spawn(poller.run());
+    }
 
     // Start agent
+    let mut agent = Agent::new(queue.clone(), ReviewResolver::from_env()?);
     tokio::spawn(async move {
-        let mut agent = Agent::new(rx);
         agent.run().await;
     });
 
     // Configure routes
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/github/webhook", post(handle_github_webhook))
-        .route("/gitlab/webhook", post(handle_gitlab_webhook));
+        .route("/gitlab/webhook", post(handle_gitlab_webhook))
+        .route("/bitbucket/webhook", post(handle_bitbucket_webhook))
+        .route("/gitea/webhook", post(handle_gitea_webhook))
+        .route("/jenkins/webhook", post(handle_jenkins_webhook))
+        .route("/metrics", get(metrics));
+
+    // Archived deliveries contain raw payloads, so browsing them requires a
+    // separate bearer token.
+    if archive_enabled && std::env::var("ARCHIVE_API_TOKEN").is_ok() {
+        app = app
+            .route("/archive/deliveries", get(list_archived_deliveries))
+            .route("/archive/deliveries/:delivery_id", get(get_archived_delivery));
+    }
+
+    let app = app.with_state(AppState {
+        queue,
+        processor,
+        metrics: Arc::new(DeliveryMetrics::default()),
+        filter,
+    });
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     axum::serve(listener, app).await?;
@@ -61,29 +171,151 @@ fn validate_secrets() -> Result<(), AppError> {
 }
 
 async fn handle_github_webhook(
+    State(state): State<AppState>,
     headers: HeaderMap,
     request: Request,
-    tx: mpsc::Sender<ci_cd_optimizer::perceiver::event::NormalizedEvent>,
 ) -> Result<Response, AppError> {
     let bytes = to_bytes(request.into_body(), usize::MAX)
         .await
         .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
-    let event = WebhookProcessor::process_github(&headers, &bytes).await?;
-    tx.send(event).await
-        .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
-    Ok(StatusCode::ACCEPTED.into_response())
+    let Some(event) = state.processor.process_github(&headers, &bytes).await? else {
+        return Ok(StatusCode::OK.into_response());
+    };
+    enqueue(&state, WebhookSource::GitHub, &headers, vec![event]).await
 }
 
 async fn handle_gitlab_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, AppError> {
+    let bytes = to_bytes(request.into_body(), usize::MAX)
+        .await
+        .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+    let events = state.processor.process_gitlab(&headers, &bytes).await?;
+    if events.is_empty() {
+        return Ok(StatusCode::OK.into_response());
+    }
+    enqueue(&state, WebhookSource::GitLab, &headers, events).await
+}
+
+async fn handle_bitbucket_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, AppError> {
+    let bytes = to_bytes(request.into_body(), usize::MAX)
+        .await
+        .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+    let Some(event) = state.processor.process_bitbucket(&headers, &bytes).await? else {
+        return Ok(StatusCode::OK.into_response());
+    };
+    enqueue(&state, WebhookSource::Bitbucket, &headers, vec![event]).await
+}
+
+async fn handle_gitea_webhook(
+    State(state): State<AppState>,
     headers: HeaderMap,
     request: Request,
-    tx: mpsc::Sender<ci_cd_optimizer::perceiver::event::NormalizedEvent>,
 ) -> Result<Response, AppError> {
     let bytes = to_bytes(request.into_body(), usize::MAX)
         .await
         .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
-    let event = WebhookProcessor::process_gitlab(&headers, &bytes).await?;
-    tx.send(event).await
+    let Some(event) = state.processor.process_gitea(&headers, &bytes).await? else {
+        return Ok(StatusCode::OK.into_response());
+    };
+    enqueue(&state, WebhookSource::Gitea, &headers, vec![event]).await
+}
+
+async fn handle_jenkins_webhook(
+    State(state): State<AppState>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, AppError> {
+    let bytes = to_bytes(request.into_body(), usize::MAX)
+        .await
         .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
-    Ok(StatusCode::ACCEPTED.into_response())
-}
\ No newline at end of file
+    let token = query.get("token").map(String::as_str);
+    let event = state.processor.process_jenkins(&headers, token, &bytes).await?;
+    enqueue(&state, WebhookSource::Jenkins, &headers, vec![event]).await
+}
+
+/// Queues the delivery's events that pass the ingestion filter, turning the
+/// delivery away with `503` and `Retry-After` when the agent has fallen too
+/// far behind.
+async fn enqueue(
+    state: &AppState,
+    source: WebhookSource,
+    headers: &HeaderMap,
+    mut events: Vec<NormalizedEvent>,
+) -> Result<Response, AppError> {
+    state.filter.retain(&mut events);
+    if events.is_empty() {
+        return Ok(StatusCode::OK.into_response());
+    }
+
+    let job_keys: Vec<String> = events.iter().filter_map(job_key).collect();
+    let result = state.queue.offer(events).await;
+
+    if let Err(e) = &result {
+        if let AppError::Saturated { limit, .. } = e {
+            tracing::warn!("Rejecting {} delivery: {} limit reached", source.as_str(), limit);
+            state.metrics.record_rejection(source, *limit);
+        }
+        // Nothing was queued, so the platform's retry must not be dropped
+        // as a duplicate.
+        state.processor.forget_delivery(source, headers, &job_keys);
+    }
+
+    result.map(|()| StatusCode::ACCEPTED.into_response())
+}
+
+async fn metrics(State(state): State<AppState>) -> String {
+    state.metrics.render(&state.queue, &state.filter)
+}
+
+fn authorize_archive(headers: &HeaderMap) -> Result<(), AppError> {
+    let expected = std::env::var("ARCHIVE_API_TOKEN")
+        .map_err(|_| AppError::ConfigError("Missing ARCHIVE_API_TOKEN".into()))?;
+
+    let token = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)?;
+
+    ci_cd_optimizer::utils::auth::verify_token(token, &expected)
+}
+
+async fn list_archived_deliveries(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_archive(&headers)?;
+    let archive = state
+        .processor
+        .archive()
+        .ok_or_else(|| AppError::ConfigError("Delivery archive is disabled".into()))?;
+
+    let page = archive.list(query).await?;
+    Ok(Json(page).into_response())
+}
+
+async fn get_archived_delivery(
+    State(state): State<AppState>,
+    Path(delivery_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_archive(&headers)?;
+    let archive = state
+        .processor
+        .archive()
+        .ok_or_else(|| AppError::ConfigError("Delivery archive is disabled".into()))?;
+
+    match archive.find_by_delivery_id(delivery_id).await? {
+        Some(delivery) => Ok(Json(delivery).into_response()),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
@@ -0,0 +1,418 @@
+use crate::errors::AppError;
+use crate::perceiver::event::NormalizedEvent;
+use crate::utils::env::env_parse;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_MAX_SEGMENTS: usize = 20;
+const DEFAULT_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+const SEGMENT_PREFIX: &str = "deliveries-";
+const SEGMENT_EXTENSION: &str = "ndjson";
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1_000;
+
+/// A webhook delivery as received, with the outcome of processing it.
+///
+/// Records are stored as NDJSON in the same shape `replay` reads, so archive
+/// segments can be replayed directly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedDelivery {
+    pub delivery_id: Option<String>,
+    pub source: String,
+    /// Receive time in seconds since the Unix epoch.
+    pub received_at: u64,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    /// `verified`, `skipped` or `failed: <reason>`.
+    pub verification: String,
+    /// Parse error, if the verified body could not be turned into events.
+    pub error: Option<String>,
+    pub events: Vec<NormalizedEvent>,
+}
+
+/// Listing entry for an archived delivery, without headers or body.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchivedDeliverySummary {
+    pub delivery_id: Option<String>,
+    pub source: String,
+    pub received_at: u64,
+    pub verification: String,
+    pub pipeline_ids: Vec<String>,
+}
+
+/// Filter and page of [`DeliveryArchive::list`], read from the query string.
+#[derive(Debug, Default, Deserialize)]
+pub struct ListQuery {
+    /// Only deliveries that produced an event for this pipeline.
+    pub pipeline_id: Option<String>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// A page of archived deliveries, newest first.
+#[derive(Debug, Serialize)]
+pub struct ArchivePage {
+    pub deliveries: Vec<ArchivedDeliverySummary>,
+    /// Cursor of the next, older, page when there is one.
+    pub next_cursor: Option<String>,
+}
+
+/// Where a record is stored: its segment file name and line. Segment names
+/// sort in creation order, so positions sort in archive order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Position {
+    segment: String,
+    line: usize,
+}
+
+impl Position {
+    fn cursor(&self) -> String {
+        format!("{}:{}", self.segment, self.line)
+    }
+
+    fn from_cursor(cursor: &str) -> Result<Self, AppError> {
+        cursor
+            .rsplit_once(':')
+            .and_then(|(segment, line)| {
+                Some(Self {
+                    segment: segment.to_string(),
+                    line: line.parse().ok()?,
+                })
+            })
+            .ok_or_else(|| AppError::BadRequest(format!("Invalid archive cursor {}", cursor)))
+    }
+}
+
+impl From<&ArchivedDelivery> for ArchivedDeliverySummary {
+    fn from(delivery: &ArchivedDelivery) -> Self {
+        let mut pipeline_ids: Vec<String> = delivery
+            .events
+            .iter()
+            .map(|e| e.pipeline_id.clone())
+            .filter(|id| !id.is_empty())
+            .collect();
+        pipeline_ids.dedup();
+
+        Self {
+            delivery_id: delivery.delivery_id.clone(),
+            source: delivery.source.clone(),
+            received_at: delivery.received_at,
+            verification: delivery.verification.clone(),
+            pipeline_ids,
+        }
+    }
+}
+
+/// Rotating on-disk store of raw webhook deliveries.
+///
+/// Deliveries are appended to NDJSON segments which rotate once they exceed
+/// the size limit; the oldest segments are removed beyond the segment count or
+/// age limits. Files are only read and written on the blocking thread pool.
+pub struct DeliveryArchive {
+    dir: PathBuf,
+    max_segment_bytes: u64,
+    max_segments: usize,
+    max_age: Duration,
+    current: Mutex<Option<Segment>>,
+}
+
+struct Segment {
+    file: File,
+    size: u64,
+}
+
+impl DeliveryArchive {
+    pub fn new(
+        dir: PathBuf,
+        max_segment_bytes: u64,
+        max_segments: usize,
+        max_age: Duration,
+    ) -> Result<Self, AppError> {
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            max_segment_bytes,
+            max_segments: max_segments.max(1),
+            max_age,
+            current: Mutex::new(None),
+        })
+    }
+
+    /// Builds an archive from `DELIVERY_ARCHIVE_DIR`, returning `None` when it
+    /// is unset. Limits come from `DELIVERY_ARCHIVE_SEGMENT_BYTES`,
+    /// `DELIVERY_ARCHIVE_MAX_SEGMENTS` and `DELIVERY_ARCHIVE_MAX_AGE_SECS`.
+    pub fn from_env() -> Result<Option<Self>, AppError> {
+        let Ok(dir) = std::env::var("DELIVERY_ARCHIVE_DIR") else {
+            return Ok(None);
+        };
+
+        let archive = Self::new(
+            PathBuf::from(dir),
+            env_parse("DELIVERY_ARCHIVE_SEGMENT_BYTES", DEFAULT_SEGMENT_BYTES)?,
+            env_parse("DELIVERY_ARCHIVE_MAX_SEGMENTS", DEFAULT_MAX_SEGMENTS)?,
+            Duration::from_secs(env_parse("DELIVERY_ARCHIVE_MAX_AGE_SECS", DEFAULT_MAX_AGE_SECS)?),
+        )?;
+
+        Ok(Some(archive))
+    }
+
+    /// Appends a delivery in the background, so recording never delays the
+    /// webhook response.
+    pub fn record(self: &Arc<Self>, delivery: ArchivedDelivery) {
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = archive.append(&delivery) {
+                warn!("Failed to archive {} delivery: {}", delivery.source, e);
+            }
+        });
+    }
+
+    /// Appends a delivery, rotating to a new segment when the current one is
+    /// full.
+    fn append(&self, delivery: &ArchivedDelivery) -> Result<(), AppError> {
+        let mut line = serde_json::to_vec(delivery).map_err(|e| AppError::Internal(e.into()))?;
+        line.push(b'\n');
+
+        let mut current = self.current.lock().unwrap();
+
+        let needs_rotation = match current.as_ref() {
+            Some(segment) => segment.size > 0 && segment.size + line.len() as u64 > self.max_segment_bytes,
+            None => true,
+        };
+        if needs_rotation {
+            *current = Some(self.open_segment()?);
+            self.enforce_retention();
+        }
+
+        if let Some(segment) = current.as_mut() {
+            segment.file.write_all(&line)?;
+            segment.size += line.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Lists a page of archived deliveries, newest first, optionally
+    /// restricted to those that produced an event for a pipeline.
+    pub async fn list(self: &Arc<Self>, query: ListQuery) -> Result<ArchivePage, AppError> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let before = query.cursor.as_deref().map(Position::from_cursor).transpose()?;
+
+        self.blocking(move |archive| {
+            // The newest `limit` matches older than the cursor.
+            let mut page = VecDeque::with_capacity(limit);
+            let mut more = false;
+            archive.scan(|position, delivery| {
+                if before.as_ref().is_some_and(|before| position >= *before) {
+                    return;
+                }
+                if !query.pipeline_id.as_deref().is_none_or(|id| produced_pipeline(&delivery, id)) {
+                    return;
+                }
+                if page.len() == limit {
+                    page.pop_front();
+                    more = true;
+                }
+                page.push_back((position, ArchivedDeliverySummary::from(&delivery)));
+            })?;
+
+            let next_cursor = page.front().filter(|_| more).map(|(position, _)| position.cursor());
+            Ok(ArchivePage {
+                deliveries: page.into_iter().rev().map(|(_, summary)| summary).collect(),
+                next_cursor,
+            })
+        })
+        .await
+    }
+
+    /// Fetches the most recent delivery with the given platform delivery ID.
+    pub async fn find_by_delivery_id(self: &Arc<Self>, delivery_id: String) -> Result<Option<ArchivedDelivery>, AppError> {
+        self.blocking(move |archive| {
+            let mut found = None;
+            archive.scan(|_, delivery| {
+                if delivery.delivery_id.as_deref() == Some(delivery_id.as_str()) {
+                    found = Some(delivery);
+                }
+            })?;
+            Ok(found)
+        })
+        .await
+    }
+
+    async fn blocking<T: Send + 'static>(
+        self: &Arc<Self>,
+        read: impl FnOnce(&Self) -> Result<T, AppError> + Send + 'static,
+    ) -> Result<T, AppError> {
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || read(&archive))
+            .await
+            .map_err(|e| AppError::Internal(e.into()))?
+    }
+
+    /// Visits every archived delivery, oldest first.
+    fn scan(&self, mut visit: impl FnMut(Position, ArchivedDelivery)) -> Result<(), AppError> {
+        for path in self.segments()? {
+            let segment = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                // Removed by retention since the directory was listed.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            for (index, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                match serde_json::from_str(&line) {
+                    Ok(delivery) => visit(
+                        Position {
+                            segment: segment.clone(),
+                            line: index,
+                        },
+                        delivery,
+                    ),
+                    Err(e) => warn!("Skipping unreadable archive record in {:?}: {}", path, e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Segment files sorted oldest first.
+    fn segments(&self) -> Result<Vec<PathBuf>, AppError> {
+        let mut segments: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| is_segment(path))
+            .collect();
+        segments.sort();
+        Ok(segments)
+    }
+
+    fn open_segment(&self) -> Result<Segment, AppError> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        // Zero-padded so lexical order matches creation order.
+        let path = self
+            .dir
+            .join(format!("{}{:020}.{}", SEGMENT_PREFIX, millis, SEGMENT_EXTENSION));
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Segment { file, size })
+    }
+
+    fn enforce_retention(&self) {
+        let segments = match self.segments() {
+            Ok(segments) => segments,
+            Err(e) => {
+                warn!("Failed to list delivery archive {:?}: {}", self.dir, e);
+                return;
+            }
+        };
+
+        let excess = segments.len().saturating_sub(self.max_segments);
+        // The newest segment is the one being written and is always kept.
+        let keep_from = segments.len().saturating_sub(1);
+
+        for (index, path) in segments.iter().enumerate().take(keep_from) {
+            let expired = std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > self.max_age);
+
+            if index < excess || expired {
+                if let Err(e) = std::fs::remove_file(path) {
+                    warn!("Failed to remove archive segment {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+fn produced_pipeline(delivery: &ArchivedDelivery, pipeline_id: &str) -> bool {
+    delivery.events.iter().any(|e| e.pipeline_id == pipeline_id)
+}
+
+fn is_segment(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    name.starts_with(SEGMENT_PREFIX) && name.ends_with(SEGMENT_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivery(id: usize) -> ArchivedDelivery {
+        ArchivedDelivery {
+            delivery_id: Some(id.to_string()),
+            source: "github".to_string(),
+            received_at: id as u64,
+            headers: HashMap::new(),
+            body: "{}".to_string(),
+            verification: "verified".to_string(),
+            error: None,
+            events: Vec::new(),
+        }
+    }
+
+    fn archive(name: &str) -> Arc<DeliveryArchive> {
+        let dir = std::env::temp_dir().join(format!("delivery-archive-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        Arc::new(DeliveryArchive::new(dir, 200, 10, Duration::from_secs(3600)).unwrap())
+    }
+
+    fn ids(page: &ArchivePage) -> Vec<String> {
+        page.deliveries.iter().filter_map(|d| d.delivery_id.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn lists_pages_newest_first_across_segments() {
+        let archive = archive("list");
+        for id in 0..5 {
+            archive.append(&delivery(id)).unwrap();
+        }
+
+        let query = |cursor| ListQuery {
+            pipeline_id: None,
+            cursor,
+            limit: Some(2),
+        };
+        let first = archive.list(query(None)).await.unwrap();
+        assert_eq!(ids(&first), ["4", "3"]);
+
+        let second = archive.list(query(first.next_cursor.clone())).await.unwrap();
+        assert_eq!(ids(&second), ["2", "1"]);
+
+        let last = archive.list(query(second.next_cursor.clone())).await.unwrap();
+        assert_eq!(ids(&last), ["0"]);
+        assert_eq!(last.next_cursor, None);
+
+        let found = archive.find_by_delivery_id("3".to_string()).await.unwrap();
+        assert_eq!(found.map(|d| d.received_at), Some(3));
+
+        std::fs::remove_dir_all(&archive.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_cursors() {
+        let archive = archive("cursor");
+        let query = ListQuery {
+            cursor: Some("not-a-cursor".to_string()),
+            ..ListQuery::default()
+        };
+        assert!(matches!(archive.list(query).await, Err(AppError::BadRequest(_))));
+        std::fs::remove_dir_all(&archive.dir).unwrap();
+    }
+}
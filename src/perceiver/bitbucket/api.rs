@@ -0,0 +1,177 @@
+use crate::errors::AppError;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_API_URL: &str = "https://api.bitbucket.org/2.0";
+
+/// Minimal Bitbucket Cloud REST client used for config loading, pipeline
+/// step and pull request lookup, pipeline re-runs and comments.
+pub struct BitbucketClient {
+    http: Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    values: Vec<T>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BitbucketPipelineStep {
+    pub uuid: String,
+    pub name: Option<String>,
+    pub state: BitbucketStepState,
+    pub pipeline: BitbucketPipelineRef,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BitbucketStepState {
+    pub name: String,
+    pub result: Option<BitbucketStepResult>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BitbucketStepResult {
+    pub name: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BitbucketPipelineRef {
+    pub uuid: String,
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    id: u64,
+    state: String,
+}
+
+/// Base URL of the Bitbucket API, from `BITBUCKET_API_URL`.
+pub fn api_url() -> String {
+    std::env::var("BITBUCKET_API_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| DEFAULT_API_URL.to_string())
+}
+
+impl BitbucketClient {
+    /// Builds a client from `BITBUCKET_API_URL` (defaults to Bitbucket Cloud)
+    /// and the optional `BITBUCKET_TOKEN` access token.
+    pub fn from_env() -> Self {
+        Self {
+            http: Client::new(),
+            base_url: api_url(),
+            token: std::env::var("BITBUCKET_TOKEN").ok(),
+        }
+    }
+
+    /// Fetches a file at `commit`, returning `None` when it does not exist.
+    pub async fn get_file(
+        &self,
+        repository: &str,
+        commit: &str,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        let url = format!("{}/repositories/{}/src/{}/{}", self.base_url, repository, commit, path);
+        let response = self.get(&url).send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Lists the steps of a pipeline identified by its build number or UUID.
+    pub async fn list_pipeline_steps(
+        &self,
+        repository: &str,
+        pipeline: &str,
+    ) -> Result<Vec<BitbucketPipelineStep>, AppError> {
+        let url = format!("{}/repositories/{}/pipelines/{}/steps/", self.base_url, repository, pipeline);
+        let page: Page<BitbucketPipelineStep> = self
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(page.values)
+    }
+
+    /// ID of the pull request `commit` belongs to, preferring an open one.
+    pub async fn pull_request_for_commit(&self, repository: &str, commit: &str) -> Result<Option<u64>, AppError> {
+        let url = format!("{}/repositories/{}/commit/{}/pullrequests", self.base_url, repository, commit);
+        let response = self.get(&url).send().await?;
+
+        // 202 means Bitbucket is still indexing the commit's pull requests.
+        if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::ACCEPTED) {
+            return Ok(None);
+        }
+
+        let page: Page<PullRequest> = response.error_for_status()?.json().await?;
+        Ok(page
+            .values
+            .iter()
+            .find(|pr| pr.state == "OPEN")
+            .or(page.values.first())
+            .map(|pr| pr.id))
+    }
+
+    /// Starts a new run of the pipeline for `commit` on `branch`. Bitbucket
+    /// has no API to re-run a single step.
+    pub async fn rerun_pipeline(&self, repository: &str, branch: &str, commit: &str) -> Result<(), AppError> {
+        let url = format!("{}/repositories/{}/pipelines/", self.base_url, repository);
+        let target = json!({
+            "target": {
+                "type": "pipeline_ref_target",
+                "ref_type": "branch",
+                "ref_name": branch,
+                "commit": { "type": "commit", "hash": commit },
+            }
+        });
+
+        self.authorize(self.http.post(&url))
+            .json(&target)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Posts a comment on a pull request.
+    pub async fn create_comment(&self, repository: &str, pr_id: u64, body: &str) -> Result<(), AppError> {
+        let url = format!("{}/repositories/{}/pullrequests/{}/comments", self.base_url, repository, pr_id);
+
+        self.authorize(self.http.post(&url))
+            .json(&json!({ "content": { "raw": body } }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// API URL serving the raw log of a pipeline step.
+    pub fn step_log_url(&self, repository: &str, pipeline_uuid: &str, step_uuid: &str) -> String {
+        format!(
+            "{}/repositories/{}/pipelines/{}/steps/{}/log",
+            self.base_url, repository, pipeline_uuid, step_uuid
+        )
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        self.authorize(self.http.get(url))
+    }
+
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+}
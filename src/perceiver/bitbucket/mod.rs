@@ -0,0 +1,29 @@
+use crate::errors::AppError;
+use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
+use api::BitbucketClient;
+
+pub mod api;
+pub mod parser;
+pub mod verifier;
+
+/// Job events for the steps of a finished Bitbucket Pipelines pipeline,
+/// looked up through the API since Bitbucket only reports pipelines as
+/// commit statuses. Other events have none.
+pub async fn step_events(event: &NormalizedEvent) -> Result<Vec<NormalizedEvent>, AppError> {
+    let finished = matches!(
+        event.event_type,
+        EventType::PipelineCompleted | EventType::PipelineErrored | EventType::PipelineCancelled
+    );
+    let build_number = event.metadata.get("pipeline_build_number");
+    let (Platform::Bitbucket, true, Some(build_number), Some(repository)) =
+        (&event.platform, finished, build_number, &event.repository)
+    else {
+        return Ok(Vec::new());
+    };
+
+    let client = BitbucketClient::from_env();
+    let steps = client.list_pipeline_steps(repository, build_number).await?;
+    parser::parse_pipeline_steps(event, &steps, |pipeline, step| {
+        client.step_log_url(repository, pipeline, step)
+    })
+}
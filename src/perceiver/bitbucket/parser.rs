@@ -0,0 +1,145 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use crate::errors::AppError;
+use crate::perceiver::bitbucket::api::BitbucketPipelineStep;
+use crate::perceiver::builder::EventBuilder;
+use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
+
+#[derive(Deserialize)]
+pub struct BitbucketCommitStatusPayload {
+    pub commit_status: BitbucketCommitStatus,
+    pub repository: BitbucketRepository,
+    pub actor: Option<BitbucketActor>,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketRepository {
+    pub full_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketActor {
+    pub display_name: Option<String>,
+    pub nickname: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketCommitStatus {
+    pub key: String,
+    pub name: Option<String>,
+    pub state: String,
+    pub url: Option<String>,
+    pub refname: Option<String>,
+    pub commit: BitbucketCommit,
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketCommit {
+    pub hash: String,
+}
+
+#[derive(Deserialize)]
+struct BitbucketGenericPayload {
+    repository: Option<BitbucketRepository>,
+}
+
+/// Routes a delivery to the parser for its `X-Event-Key` header.
+///
+/// Event keys without a dedicated parser become `EventType::Unknown` events.
+pub fn parse_event(event_key: &str, payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let mut event = match event_key {
+        "repo:commit_status_created" | "repo:commit_status_updated" => parse_commit_status(payload)?,
+        _ => {
+            let payload: Option<BitbucketGenericPayload> = serde_json::from_slice(payload).ok();
+            EventBuilder::new(Platform::Bitbucket, EventType::Unknown)
+                .repository(payload.and_then(|p| p.repository).map(|r| r.full_name))
+                .build()?
+        }
+    };
+
+    event.raw_event_type = Some(event_key.to_string());
+    Ok(event)
+}
+
+pub fn parse_commit_status(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: BitbucketCommitStatusPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Bitbucket commit status payload: {}", e)))?;
+
+    let status = &payload.commit_status;
+
+    let event_type = match status.state.as_str() {
+        "SUCCESSFUL" => EventType::PipelineCompleted,
+        "FAILED" => EventType::PipelineErrored,
+        "STOPPED" => EventType::PipelineCancelled,
+        _ => EventType::Unknown,
+    };
+
+    // Statuses posted by Bitbucket Pipelines link to the pipeline result page,
+    // which carries the build number the API addresses pipelines by.
+    let build_number = status.url.as_deref().and_then(pipeline_build_number);
+    let pipeline_id = build_number.clone().unwrap_or_else(|| status.key.clone());
+
+    let actor = payload
+        .actor
+        .as_ref()
+        .and_then(|a| a.nickname.clone().or(a.display_name.clone()));
+
+    EventBuilder::new(Platform::Bitbucket, event_type)
+        .pipeline_id(pipeline_id)
+        .repository(payload.repository.full_name.clone())
+        .commit_sha(status.commit.hash.clone())
+        .branch(status.refname.clone())
+        .actor(actor)
+        .metadata("status_key", status.key.clone())
+        .metadata("status", status.state.clone())
+        .metadata("status_name", status.name.clone())
+        .metadata("details_url", status.url.clone())
+        .metadata("pipeline_build_number", build_number)
+        .build()
+}
+
+/// Expands a pipeline-level event into one job event per pipeline step.
+///
+/// `log_url` maps `(pipeline_uuid, step_uuid)` to the step's log endpoint.
+pub fn parse_pipeline_steps(
+    pipeline_event: &NormalizedEvent,
+    steps: &[BitbucketPipelineStep],
+    log_url: impl Fn(&str, &str) -> String,
+) -> Result<Vec<NormalizedEvent>, AppError> {
+    steps
+        .iter()
+        .map(|step| {
+            let result = step.state.result.as_ref().map(|r| r.name.as_str());
+            let event_type = match (step.state.name.as_str(), result) {
+                (_, Some("SUCCESSFUL")) => EventType::JobSucceeded,
+                (_, Some("STOPPED")) => EventType::JobCancelled,
+                (_, Some("NOT_RUN")) => EventType::JobSkipped,
+                (_, Some(_)) => EventType::JobFailed,
+                ("PENDING", None) | ("IN_PROGRESS", None) => EventType::JobStarted,
+                _ => EventType::Unknown,
+            };
+
+            EventBuilder::new(Platform::Bitbucket, event_type)
+                .context_from(pipeline_event)
+                .pipeline_id(pipeline_event.pipeline_id.clone())
+                .job_id(step.uuid.clone())
+                .logs_uri(log_url(&step.pipeline.uuid, &step.uuid))
+                .raw_event_type("pipeline_step".to_string())
+                .job_name(step.name.clone())
+                .metadata("pipeline_uuid", step.pipeline.uuid.clone())
+                .metadata("status", result.map(str::to_string))
+                .build()
+        })
+        .collect()
+}
+
+fn pipeline_build_number(url: &str) -> Option<String> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| Regex::new(r"pipelines/results/(\d+)|#!/results/(\d+)").unwrap());
+    let captures = pattern.captures(url)?;
+    captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .map(|m| m.as_str().to_string())
+}
@@ -0,0 +1,11 @@
+use crate::errors::AppError;
+
+/// Bitbucket Cloud signs deliveries with the same `sha256=<hex>` HMAC scheme
+/// as GitHub, sent in the `X-Hub-Signature` header.
+pub fn verify_signature(
+    payload: &[u8],
+    signature: &str,
+    secret: &str,
+) -> Result<(), AppError> {
+    crate::perceiver::github::verifier::verify_signature(payload, signature, secret)
+}
@@ -0,0 +1,284 @@
+use chrono::{DateTime, Utc};
+use crate::errors::AppError;
+use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
+use tracing::warn;
+
+/// Builds a [`NormalizedEvent`] from typed fields, so every platform parser
+/// fills the same fields the same way.
+///
+/// Setters accept either a value or an `Option`, letting parsers pass
+/// optional payload fields straight through. [`EventBuilder::build`] checks
+/// the fields the event type requires, and drops timestamps that contradict
+/// each other rather than rejecting the event over them.
+pub struct EventBuilder {
+    event: NormalizedEvent,
+}
+
+impl EventBuilder {
+    pub fn new(platform: Platform, event_type: EventType) -> Self {
+        Self {
+            event: NormalizedEvent::new(platform, String::new(), None, event_type, None),
+        }
+    }
+
+    pub fn pipeline_id(mut self, pipeline_id: impl Into<String>) -> Self {
+        self.event.pipeline_id = pipeline_id.into();
+        self.event.platform_id = self.event.platform.event_id(&self.event.pipeline_id);
+        self
+    }
+
+    pub fn job_id(mut self, job_id: impl Into<Option<String>>) -> Self {
+        self.event.job_id = job_id.into();
+        self
+    }
+
+    pub fn logs_uri(mut self, logs_uri: impl Into<Option<String>>) -> Self {
+        self.event.logs_uri = logs_uri.into();
+        self
+    }
+
+    pub fn repository(mut self, repository: impl Into<Option<String>>) -> Self {
+        self.event.repository = repository.into();
+        self
+    }
+
+    pub fn instance_url(mut self, instance_url: impl Into<Option<String>>) -> Self {
+        self.event.instance_url = instance_url.into();
+        self
+    }
+
+    pub fn commit_sha(mut self, commit_sha: impl Into<Option<String>>) -> Self {
+        self.event.commit_sha = commit_sha.into();
+        self
+    }
+
+    pub fn branch(mut self, branch: impl Into<Option<String>>) -> Self {
+        self.event.branch = branch.into();
+        self
+    }
+
+    pub fn pr_number(mut self, pr_number: impl Into<Option<u64>>) -> Self {
+        self.event.pr_number = pr_number.into();
+        self
+    }
+
+    pub fn job_name(mut self, job_name: impl Into<Option<String>>) -> Self {
+        self.event.job_name = job_name.into();
+        self
+    }
+
+    pub fn stage(mut self, stage: impl Into<Option<String>>) -> Self {
+        self.event.stage = stage.into();
+        self
+    }
+
+    pub fn attempt(mut self, attempt: impl Into<Option<u32>>) -> Self {
+        self.event.attempt = attempt.into();
+        self
+    }
+
+    pub fn actor(mut self, actor: impl Into<Option<String>>) -> Self {
+        self.event.actor = actor.into();
+        self
+    }
+
+    pub fn created_at(mut self, created_at: impl Into<Option<DateTime<Utc>>>) -> Self {
+        self.event.created_at = created_at.into();
+        self
+    }
+
+    pub fn started_at(mut self, started_at: impl Into<Option<DateTime<Utc>>>) -> Self {
+        self.event.started_at = started_at.into();
+        self
+    }
+
+    pub fn completed_at(mut self, completed_at: impl Into<Option<DateTime<Utc>>>) -> Self {
+        self.event.completed_at = completed_at.into();
+        self
+    }
+
+    pub fn raw_event_type(mut self, raw_event_type: impl Into<Option<String>>) -> Self {
+        self.event.raw_event_type = raw_event_type.into();
+        self
+    }
+
+    pub fn trigger_source(mut self, trigger_source: impl Into<Option<String>>) -> Self {
+        self.event.trigger_source = trigger_source.into();
+        self
+    }
+
+    /// Adds a platform-specific detail; `None` values are skipped.
+    pub fn metadata(mut self, key: &str, value: impl Into<Option<String>>) -> Self {
+        if let Some(value) = value.into() {
+            self.event.metadata.insert(key.to_string(), value);
+        }
+        self
+    }
+
+    /// Copies the repository, commit, review and trigger context of a parent
+    /// event, for job events expanded from a pipeline event.
+    pub fn context_from(mut self, parent: &NormalizedEvent) -> Self {
+        self.event.repository = parent.repository.clone();
+        self.event.instance_url = parent.instance_url.clone();
+        self.event.commit_sha = parent.commit_sha.clone();
+        self.event.branch = parent.branch.clone();
+        self.event.pr_number = parent.pr_number;
+        self.event.actor = parent.actor.clone();
+        self.event.trigger_source = parent.trigger_source.clone();
+        self.event.metadata = parent.metadata.clone();
+        self
+    }
+
+    /// Validates the event and returns it.
+    ///
+    /// Classified events need a pipeline ID, and job events a job ID. Events
+    /// from repository-hosted platforms also need the repository and commit
+    /// they ran for; Jenkins builds need not be tied to either.
+    pub fn build(self) -> Result<NormalizedEvent, AppError> {
+        let mut event = self.event;
+
+        if event.event_type == EventType::Unknown {
+            return Ok(event);
+        }
+
+        let missing = |field: &str| {
+            AppError::BadRequest(format!(
+                "Invalid {:?} {:?} event: missing {}",
+                event.platform, event.event_type, field
+            ))
+        };
+
+        if event.pipeline_id.is_empty() {
+            return Err(missing("pipeline ID"));
+        }
+
+        if event.event_type.is_job_event() && event.job_id.is_none() {
+            return Err(missing("job ID"));
+        }
+
+        if event.platform != Platform::Jenkins {
+            if event.repository.is_none() {
+                return Err(missing("repository"));
+            }
+            if event.commit_sha.is_none() {
+                return Err(missing("commit SHA"));
+            }
+        }
+
+        if event.attempt == Some(0) {
+            return Err(AppError::BadRequest("Event attempt numbers start at 1".into()));
+        }
+
+        let in_order = |earlier: Option<DateTime<Utc>>, later: Option<DateTime<Utc>>| match (earlier, later) {
+            (Some(earlier), Some(later)) => earlier <= later,
+            _ => true,
+        };
+        if !in_order(event.started_at, event.completed_at) {
+            warn!("Event {} completed before it started; dropping both times", event.platform_id);
+            event.started_at = None;
+            event.completed_at = None;
+        }
+        if !in_order(event.created_at, event.started_at) || !in_order(event.created_at, event.completed_at) {
+            warn!("Event {} was created after it ran; dropping its creation time", event.platform_id);
+            event.created_at = None;
+        }
+
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn job(platform: Platform) -> EventBuilder {
+        EventBuilder::new(platform, EventType::JobFailed)
+            .pipeline_id("12")
+            .job_id("34".to_string())
+            .repository("acme/widgets".to_string())
+            .commit_sha("abc123".to_string())
+    }
+
+    fn rejects(builder: EventBuilder, field: &str) {
+        match builder.build() {
+            Err(AppError::BadRequest(message)) => assert!(message.contains(field), "{}", message),
+            other => panic!("expected a missing {}, got {:?}", field, other.map(|e| e.event_type)),
+        }
+    }
+
+    #[test]
+    fn requires_the_fields_of_classified_events() {
+        assert!(job(Platform::GitHub).build().is_ok());
+
+        rejects(job(Platform::GitHub).pipeline_id(""), "pipeline ID");
+        rejects(job(Platform::GitHub).job_id(None), "job ID");
+        rejects(job(Platform::GitHub).repository(None), "repository");
+        rejects(job(Platform::GitLab).commit_sha(None), "commit SHA");
+    }
+
+    #[test]
+    fn lets_jenkins_builds_omit_the_repository() {
+        let event = job(Platform::Jenkins).repository(None).commit_sha(None).build().unwrap();
+        assert_eq!(event.platform_id, Platform::Jenkins.event_id("12"));
+    }
+
+    #[test]
+    fn does_not_validate_unknown_events() {
+        let event = EventBuilder::new(Platform::GitHub, EventType::Unknown).build().unwrap();
+        assert!(event.pipeline_id.is_empty());
+    }
+
+    #[test]
+    fn rejects_attempt_zero() {
+        assert!(matches!(job(Platform::GitHub).attempt(0).build(), Err(AppError::BadRequest(_))));
+        assert_eq!(job(Platform::GitHub).attempt(1).build().unwrap().attempt, Some(1));
+    }
+
+    #[test]
+    fn drops_contradictory_timestamps() {
+        let event = job(Platform::GitHub)
+            .created_at(at("2024-05-01T10:00:00Z"))
+            .started_at(at("2024-05-01T10:05:00Z"))
+            .completed_at(at("2024-05-01T10:01:00Z"))
+            .build()
+            .unwrap();
+        assert_eq!(event.created_at, Some(at("2024-05-01T10:00:00Z")));
+        assert_eq!(event.started_at, None);
+        assert_eq!(event.completed_at, None);
+
+        let event = job(Platform::GitHub)
+            .created_at(at("2024-05-01T10:10:00Z"))
+            .started_at(at("2024-05-01T10:05:00Z"))
+            .completed_at(at("2024-05-01T10:15:00Z"))
+            .build()
+            .unwrap();
+        assert_eq!(event.created_at, None);
+        assert_eq!(event.started_at, Some(at("2024-05-01T10:05:00Z")));
+        assert_eq!(event.completed_at, Some(at("2024-05-01T10:15:00Z")));
+    }
+
+    #[test]
+    fn skips_empty_metadata_and_copies_parent_context() {
+        let parent = job(Platform::GitLab)
+            .pr_number(7)
+            .metadata("source", "merge_request_event".to_string())
+            .metadata("missing", None)
+            .build()
+            .unwrap();
+        assert!(!parent.metadata.contains_key("missing"));
+
+        let child = EventBuilder::new(Platform::GitLab, EventType::JobSucceeded)
+            .context_from(&parent)
+            .pipeline_id("12")
+            .job_id("35".to_string())
+            .build()
+            .unwrap();
+        assert_eq!(child.repository.as_deref(), Some("acme/widgets"));
+        assert_eq!(child.pr_number, Some(7));
+        assert_eq!(child.metadata.get("source").map(String::as_str), Some("merge_request_event"));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::errors::AppError;
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use crate::config::instance::{PlatformInstance, PlatformInstances};
+use crate::perceiver::filter::EventFilter;
+use crate::perceiver::queue::EventQueue;
+use crate::perceiver::{github, gitlab};
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+const PAGE_SIZE: &str = "20";
+/// Jobs requested per page when listing a run's jobs.
+const JOBS_PAGE_SIZE: usize = 100;
+
+/// Settings for the polling perceiver, used where inbound webhooks cannot
+/// reach the agent.
+#[derive(Debug, Clone)]
+pub struct PollerConfig {
+    pub interval: Duration,
+    /// GitHub repositories as `owner/name`.
+    pub github_repos: Vec<String>,
+    /// GitLab projects as `group/project` paths.
+    pub gitlab_projects: Vec<String>,
+    /// File the last-seen run state is persisted to between restarts.
+    pub cursor_path: Option<PathBuf>,
+}
+
+impl PollerConfig {
+    /// Reads `POLL_INTERVAL_SECS`, `POLL_GITHUB_REPOS`, `POLL_GITLAB_PROJECTS`
+    /// (comma-separated) and `POLL_CURSOR_PATH`. Repositories are polled on
+    /// the default GitHub and GitLab instances.
+    pub fn from_env() -> Result<Self, AppError> {
+        let interval = match std::env::var("POLL_INTERVAL_SECS") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| AppError::ConfigError(format!("Invalid POLL_INTERVAL_SECS: {}", value)))?,
+            Err(_) => DEFAULT_POLL_INTERVAL_SECS,
+        };
+
+        Ok(Self {
+            interval: Duration::from_secs(interval),
+            github_repos: env_list("POLL_GITHUB_REPOS"),
+            gitlab_projects: env_list("POLL_GITLAB_PROJECTS"),
+            cursor_path: std::env::var("POLL_CURSOR_PATH").ok().map(PathBuf::from),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.github_repos.is_empty() || !self.gitlab_projects.is_empty()
+    }
+}
+
+/// Last observed state of every run, keyed by source (`github:<repo>` or
+/// `gitlab:<project>`) and then by run / pipeline ID.
+type Cursor = HashMap<String, HashMap<String, String>>;
+
+/// A source's events from one poll, with the cursor entry to record for it
+/// once they are queued.
+struct Polled {
+    source: String,
+    runs: HashMap<String, String>,
+    events: Vec<NormalizedEvent>,
+}
+
+/// Periodically lists recent GitHub workflow runs and GitLab pipelines and
+/// pushes events for every run whose status changed since the last poll.
+pub struct Poller {
+    config: PollerConfig,
+    queue: Arc<EventQueue>,
+    filter: Arc<EventFilter>,
+    cursor: Cursor,
+}
+
+#[derive(Deserialize)]
+struct GitHubRunList {
+    workflow_runs: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct GitHubJobList {
+    jobs: Vec<Value>,
+}
+
+impl Poller {
+    pub fn new(config: PollerConfig, queue: Arc<EventQueue>, filter: Arc<EventFilter>) -> Result<Self, AppError> {
+        let cursor = match &config.cursor_path {
+            Some(path) if path.exists() => {
+                let bytes = std::fs::read(path)?;
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| AppError::ConfigError(format!("Invalid poll cursor {:?}: {}", path, e)))?
+            }
+            _ => Cursor::new(),
+        };
+
+        Ok(Self {
+            config,
+            queue,
+            filter,
+            cursor,
+        })
+    }
+
+    /// Polls on the configured interval, forever.
+    pub async fn run(mut self) {
+        let mut ticker = tokio::time::interval(self.config.interval);
+
+        loop {
+            ticker.tick().await;
+
+            // A source's runs are only marked as seen once its events are
+            // built and queued, so a failure re-polls them next time.
+            let mut cursor = self.cursor.clone();
+            let mut events = Vec::new();
+            for repo in self.config.github_repos.clone() {
+                match self.poll_github(&repo).await {
+                    Ok(polled) => {
+                        cursor.insert(polled.source, polled.runs);
+                        events.extend(polled.events);
+                    }
+                    Err(e) => warn!("Failed to poll GitHub repository {}: {}", repo, e),
+                }
+            }
+            for project in self.config.gitlab_projects.clone() {
+                match self.poll_gitlab(&project).await {
+                    Ok(polled) => {
+                        cursor.insert(polled.source, polled.runs);
+                        events.extend(polled.events);
+                    }
+                    Err(e) => warn!("Failed to poll GitLab project {}: {}", project, e),
+                }
+            }
+
+            self.filter.retain(&mut events);
+            if let Err(e) = self.queue.push_all(events).await {
+                warn!("Failed to queue polled events: {}", e);
+                continue;
+            }
+
+            self.cursor = cursor;
+            self.save_cursor();
+        }
+    }
+
+    async fn poll_github(&self, repo: &str) -> Result<Polled, AppError> {
+        let instance = PlatformInstances::global()?.resolve(&Platform::GitHub, None)?;
+        let url = format!("{}/repos/{}/actions/runs", instance.api_url(), repo);
+        let list: GitHubRunList = github_get(instance, repo, &url)
+            .await?
+            .query(&[("per_page", PAGE_SIZE)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let repository = json!({
+            "full_name": repo,
+            "html_url": format!("{}/{}", instance.web_url(), repo),
+        });
+        let source = format!("github:{}", repo);
+        let mut events = Vec::new();
+
+        let (changed, runs) = self.changed_runs(&source, &list.workflow_runs, |run| {
+            let status = run["status"].as_str().unwrap_or_default();
+            let conclusion = run["conclusion"].as_str().unwrap_or_default();
+            (format!("{}:{}", status, conclusion), status == "completed")
+        });
+        for (run, completed) in changed {
+            let payload = json!({
+                "action": if completed { "completed" } else { "in_progress" },
+                "workflow_run": run,
+                "repository": repository,
+            });
+            events.push(github::parser::parse_workflow_run(&to_bytes(&payload)?)?);
+
+            if completed {
+                let jobs_url = format!("{}/jobs", run["url"].as_str().unwrap_or_default());
+                for job in github_jobs(instance, repo, &jobs_url).await? {
+                    let payload = json!({ "workflow_job": job, "repository": repository });
+                    events.push(github::parser::parse_workflow_job(&to_bytes(&payload)?)?);
+                }
+            }
+        }
+
+        Ok(Polled { source, runs, events })
+    }
+
+    async fn poll_gitlab(&self, project: &str) -> Result<Polled, AppError> {
+        let instance = PlatformInstances::global()?.resolve(&Platform::GitLab, None)?;
+        let project_url = format!("{}/projects/{}", instance.api_url(), project.replace('/', "%2F"));
+        let pipelines: Vec<Value> = gitlab_get(instance, project, &format!("{}/pipelines", project_url))
+            .await?
+            .query(&[("per_page", PAGE_SIZE), ("order_by", "updated_at")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let source = format!("gitlab:{}", project);
+        let mut events = Vec::new();
+
+        let (changed, runs) = self.changed_runs(&source, &pipelines, |pipeline| {
+            let status = pipeline["status"].as_str().unwrap_or_default();
+            let finished = matches!(status, "success" | "failed" | "canceled" | "skipped");
+            (status.to_string(), finished)
+        });
+        for (pipeline, finished) in changed {
+            let builds = if finished {
+                let jobs_url = format!("{}/pipelines/{}/jobs", project_url, pipeline["id"]);
+                gitlab_jobs(instance, project, &jobs_url).await?
+            } else {
+                Vec::new()
+            };
+
+            // Pipeline pages link to `<project web url>/-/pipelines/<id>`.
+            let web_url = pipeline["web_url"].as_str().unwrap_or_default();
+            let project_web_url = web_url.split("/-/").next().unwrap_or(web_url);
+
+            let payload = json!({
+                "object_attributes": pipeline,
+                "project": {
+                    "web_url": project_web_url,
+                    "path_with_namespace": project,
+                },
+                "builds": builds,
+            });
+            events.extend(gitlab::parser::parse_pipeline(&to_bytes(&payload)?)?);
+        }
+
+        Ok(Polled { source, runs, events })
+    }
+
+    /// Diffs `runs` against the cursor for `source`, returning the runs whose
+    /// state changed together with whether they have finished, and the
+    /// cursor entry recording every run's current state.
+    ///
+    /// The first poll of a source only seeds the cursor, so historic runs are
+    /// not replayed as new events.
+    fn changed_runs(
+        &self,
+        source: &str,
+        runs: &[Value],
+        state_of: impl Fn(&Value) -> (String, bool),
+    ) -> (Vec<(Value, bool)>, HashMap<String, String>) {
+        let previous = self.cursor.get(source);
+
+        let mut current = HashMap::new();
+        let mut changed = Vec::new();
+
+        for run in runs {
+            let id = run["id"].to_string();
+            let (state, finished) = state_of(run);
+
+            if previous.is_some_and(|previous| previous.get(&id) != Some(&state)) {
+                changed.push((run.clone(), finished));
+            }
+            current.insert(id, state);
+        }
+
+        (changed, current)
+    }
+
+    fn save_cursor(&self) {
+        let Some(path) = &self.config.cursor_path else {
+            return;
+        };
+
+        let result = serde_json::to_vec(&self.cursor)
+            .map_err(std::io::Error::other)
+            .and_then(|bytes| std::fs::write(path, bytes));
+
+        if let Err(e) = result {
+            warn!("Failed to persist poll cursor to {:?}: {}", path, e);
+        }
+    }
+}
+
+async fn github_get(instance: &PlatformInstance, repo: &str, url: &str) -> Result<RequestBuilder, AppError> {
+    let req = instance
+        .http()
+        .get(url)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "ci-cd-optimizer");
+    Ok(match instance.token_for(repo, None).await? {
+        Some(token) => req.bearer_auth(token),
+        None => req,
+    })
+}
+
+/// Every job of a workflow run, following pagination.
+async fn github_jobs(instance: &PlatformInstance, repo: &str, jobs_url: &str) -> Result<Vec<Value>, AppError> {
+    let mut jobs = Vec::new();
+
+    for page in 1.. {
+        let list: GitHubJobList = github_get(instance, repo, jobs_url)
+            .await?
+            .query(&[("per_page", JOBS_PAGE_SIZE), ("page", page)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let last = list.jobs.len() < JOBS_PAGE_SIZE;
+        jobs.extend(list.jobs);
+        if last {
+            break;
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// Every job of a pipeline, following pagination.
+async fn gitlab_jobs(instance: &PlatformInstance, project: &str, jobs_url: &str) -> Result<Vec<Value>, AppError> {
+    let mut jobs = Vec::new();
+
+    for page in 1.. {
+        let list: Vec<Value> = gitlab_get(instance, project, jobs_url)
+            .await?
+            .query(&[("per_page", JOBS_PAGE_SIZE), ("page", page)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let last = list.len() < JOBS_PAGE_SIZE;
+        jobs.extend(list);
+        if last {
+            break;
+        }
+    }
+
+    Ok(jobs)
+}
+
+async fn gitlab_get(instance: &PlatformInstance, project: &str, url: &str) -> Result<RequestBuilder, AppError> {
+    let req = instance.http().get(url);
+    Ok(match instance.token_for(project, None).await? {
+        Some(token) => req.header("PRIVATE-TOKEN", token),
+        None => req,
+    })
+}
+
+fn to_bytes(payload: &Value) -> Result<Vec<u8>, AppError> {
+    serde_json::to_vec(payload).map_err(|e| AppError::Internal(e.into()))
+}
+
+fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,273 @@
+use crate::errors::AppError;
+use crate::utils::env::env_parse;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Bounded, TTL-based record of webhook delivery IDs that have already been
+/// accepted, so platform redeliveries are not processed twice.
+///
+/// When a persistence path is configured every recorded ID is appended to that
+/// file as `<unix_seconds> <key>`, and every forgotten one as `forget <key>`,
+/// and the file is replayed on startup.
+pub struct DeliveryDeduplicator {
+    capacity: usize,
+    ttl: Duration,
+    state: Arc<Mutex<SeenSet>>,
+    store: Option<Arc<DeliveryLog>>,
+}
+
+#[derive(Default)]
+struct SeenSet {
+    seen: HashMap<String, u64>,
+    order: VecDeque<(String, u64)>,
+}
+
+/// Marks a forgotten key in the persistence file.
+const FORGET: &str = "forget";
+
+struct DeliveryLog {
+    path: PathBuf,
+    /// Locked while the file is compacted, so no record is appended to the
+    /// file being replaced.
+    file: Mutex<File>,
+    appended: Mutex<usize>,
+}
+
+impl DeliveryDeduplicator {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            state: Arc::new(Mutex::new(SeenSet::default())),
+            store: None,
+        }
+    }
+
+    /// Creates a deduplicator backed by an append-only file, restoring any
+    /// IDs recorded within the TTL.
+    pub fn with_persistence(capacity: usize, ttl: Duration, path: PathBuf) -> Result<Self, AppError> {
+        let mut dedup = Self::new(capacity, ttl);
+        let now = unix_now();
+
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            let mut state = dedup.state.lock().unwrap();
+            for line in reader.lines() {
+                let line = line?;
+                let Some((ts, key)) = line.split_once(' ') else {
+                    continue;
+                };
+                if ts == FORGET {
+                    state.seen.remove(key);
+                    continue;
+                }
+                let Ok(ts) = ts.parse::<u64>() else {
+                    continue;
+                };
+                if ts + ttl.as_secs() > now && !state.seen.contains_key(key) {
+                    state.seen.insert(key.to_string(), ts);
+                    state.order.push_back((key.to_string(), ts));
+                }
+            }
+            while state.order.len() > capacity {
+                if let Some((key, ts)) = state.order.pop_front() {
+                    if state.seen.get(&key) == Some(&ts) {
+                        state.seen.remove(&key);
+                    }
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let store = Arc::new(DeliveryLog {
+            path,
+            file: Mutex::new(file),
+            appended: Mutex::new(0),
+        });
+        store.rewrite(&dedup.state);
+        dedup.store = Some(store);
+
+        Ok(dedup)
+    }
+
+    /// Builds a deduplicator from `WEBHOOK_DEDUP_CAPACITY`,
+    /// `WEBHOOK_DEDUP_TTL_SECS` and the optional `WEBHOOK_DEDUP_PATH`.
+    pub fn from_env() -> Result<Self, AppError> {
+        let capacity = env_parse("WEBHOOK_DEDUP_CAPACITY", DEFAULT_CAPACITY)?;
+        let ttl = Duration::from_secs(env_parse("WEBHOOK_DEDUP_TTL_SECS", DEFAULT_TTL_SECS)?);
+
+        match std::env::var("WEBHOOK_DEDUP_PATH") {
+            Ok(path) => Self::with_persistence(capacity, ttl, PathBuf::from(path)),
+            Err(_) => Ok(Self::new(capacity, ttl)),
+        }
+    }
+
+    /// Records `key` and returns `true` if it had not been seen within the TTL.
+    pub fn check_and_record(&self, key: &str) -> bool {
+        let now = unix_now();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.evict_expired(now, self.ttl.as_secs());
+
+            if state.seen.contains_key(key) {
+                return false;
+            }
+
+            state.seen.insert(key.to_string(), now);
+            state.order.push_back((key.to_string(), now));
+            while state.order.len() > self.capacity {
+                if let Some((old, ts)) = state.order.pop_front() {
+                    if state.seen.get(&old) == Some(&ts) {
+                        state.seen.remove(&old);
+                    }
+                }
+            }
+        }
+
+        self.persist(key, now);
+        true
+    }
+
+    /// Forgets `key`, so a later delivery with the same ID is processed,
+    /// also after a restart.
+    pub fn forget(&self, key: &str) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.seen.remove(key).is_none() {
+                return;
+            }
+            state.order.retain(|(seen, _)| seen != key);
+        }
+
+        self.append(format_args!("{} {}", FORGET, key));
+    }
+
+    fn persist(&self, key: &str, ts: u64) {
+        self.append(format_args!("{} {}", ts, key));
+    }
+
+    fn append(&self, record: std::fmt::Arguments) {
+        let Some(store) = &self.store else {
+            return;
+        };
+
+        if let Err(e) = writeln!(store.file.lock().unwrap(), "{}", record) {
+            warn!("Failed to persist delivery record {}: {}", record, e);
+            return;
+        }
+
+        let needs_compaction = {
+            let mut appended = store.appended.lock().unwrap();
+            *appended += 1;
+            let needed = *appended > self.capacity;
+            if needed {
+                *appended = 0;
+            }
+            needed
+        };
+        if needs_compaction {
+            self.compact(store);
+        }
+    }
+
+    /// Rewrites the persistence file with only the IDs currently retained,
+    /// on the blocking thread pool when called from the runtime.
+    fn compact(&self, store: &Arc<DeliveryLog>) {
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let (store, state) = (store.clone(), self.state.clone());
+                runtime.spawn_blocking(move || store.rewrite(&state));
+            }
+            Err(_) => store.rewrite(&self.state),
+        }
+    }
+}
+
+impl DeliveryLog {
+    /// Replaces the file with the IDs retained in `state`. Records appended
+    /// meanwhile wait for the file lock and go to the new file.
+    fn rewrite(&self, state: &Mutex<SeenSet>) {
+        let mut file = self.file.lock().unwrap();
+        let contents = state.lock().unwrap().retained();
+
+        let tmp_path = self.path.with_extension("tmp");
+        let result = std::fs::write(&tmp_path, contents)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .and_then(|_| OpenOptions::new().append(true).open(&self.path));
+
+        match result {
+            Ok(reopened) => *file = reopened,
+            Err(e) => warn!("Failed to compact delivery log {:?}: {}", self.path, e),
+        }
+    }
+}
+
+impl SeenSet {
+    /// The persistence file's contents for the IDs currently retained.
+    fn retained(&self) -> String {
+        self.order
+            .iter()
+            .filter(|(key, ts)| self.seen.get(key) == Some(ts))
+            .map(|(key, ts)| format!("{} {}\n", ts, key))
+            .collect()
+    }
+
+    fn evict_expired(&mut self, now: u64, ttl_secs: u64) {
+        while let Some((_, ts)) = self.order.front() {
+            if ts + ttl_secs > now {
+                break;
+            }
+            if let Some((key, ts)) = self.order.pop_front() {
+                if self.seen.get(&key) == Some(&ts) {
+                    self.seen.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgotten_deliveries_stay_forgotten_after_a_restart() {
+        let path = std::env::temp_dir().join(format!("delivery-dedup-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let ttl = Duration::from_secs(60);
+
+        let dedup = DeliveryDeduplicator::with_persistence(10, ttl, path.clone()).unwrap();
+        assert!(dedup.check_and_record("github:accepted"));
+        assert!(dedup.check_and_record("github:rejected"));
+        dedup.forget("github:rejected");
+        drop(dedup);
+
+        let restarted = DeliveryDeduplicator::with_persistence(10, ttl, path.clone()).unwrap();
+        assert!(!restarted.check_and_record("github:accepted"));
+        assert!(restarted.check_and_record("github:rejected"));
+        drop(restarted);
+
+        // Compacted on the restart, the file no longer holds the tombstone,
+        // and the retried delivery was recorded again.
+        let again = DeliveryDeduplicator::with_persistence(10, ttl, path.clone()).unwrap();
+        assert!(!again.check_and_record("github:rejected"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,12 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Platform {
     GitHub,
     GitLab,
+    Bitbucket,
+    Gitea,
+    Jenkins,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Platform {
+    /// Platform-qualified ID for a pipeline, e.g. `github#<run_id>`.
+    pub fn event_id(&self, pipeline_id: &str) -> String {
+        match self {
+            Platform::GitHub => format!("github#{}", pipeline_id),
+            Platform::GitLab => format!("gitlab#{}", pipeline_id),
+            Platform::Bitbucket => format!("bitbucket#{}", pipeline_id),
+            Platform::Gitea => format!("gitea#{}", pipeline_id),
+            Platform::Jenkins => format!("jenkins#{}", pipeline_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
     /// A job has started execution (e.g., GitHub `workflow_job`, GitLab `job`).
     JobStarted,
@@ -14,6 +33,17 @@ pub enum EventType {
     JobSucceeded,
     /// A job has failed.
     JobFailed,
+    /// A job was cancelled before finishing, usually by a person.
+    JobCancelled,
+    /// A job was skipped by a condition and never ran.
+    JobSkipped,
+    /// A job ran past its time limit and was stopped.
+    JobTimedOut,
+    /// A job finished but needs someone to act on it (e.g. a GitHub check
+    /// asking for approval).
+    JobActionRequired,
+    /// A job is waiting to be started by hand or for an approval gate.
+    JobManual,
     /// The entire pipeline has completed successfully.
     PipelineCompleted,
     /// A test failed within a job (e.g., `check_suite` failure).
@@ -22,11 +52,49 @@ pub enum EventType {
     DependencyIssue,
     /// The entire pipeline has encountered a critical error and did not complete.
     PipelineErrored,
+    /// The pipeline was cancelled before it completed.
+    PipelineCancelled,
+    /// The pipeline ran past its time limit and was stopped.
+    PipelineTimedOut,
+    /// The pipeline finished but needs someone to act on it (e.g. a GitHub
+    /// workflow run awaiting approval).
+    PipelineActionRequired,
     /// An unrecognized or unclassified event type.
     Unknown,
 }
 
-#[derive(Debug, Clone)]
+impl EventType {
+    /// Whether the event describes a single job rather than a pipeline.
+    pub fn is_job_event(&self) -> bool {
+        matches!(
+            self,
+            EventType::JobStarted
+                | EventType::JobSucceeded
+                | EventType::JobFailed
+                | EventType::JobCancelled
+                | EventType::JobSkipped
+                | EventType::JobTimedOut
+                | EventType::JobActionRequired
+                | EventType::JobManual
+        )
+    }
+
+    /// Whether the work was stopped or held on purpose, by a person or a
+    /// workflow condition. Such work must never be retried automatically.
+    pub fn is_intentional_stop(&self) -> bool {
+        matches!(
+            self,
+            EventType::JobCancelled
+                | EventType::JobSkipped
+                | EventType::JobActionRequired
+                | EventType::JobManual
+                | EventType::PipelineCancelled
+                | EventType::PipelineActionRequired
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NormalizedEvent {
     pub platform: Platform,
     pub platform_id: String, // GitHub: <repo>#<run_id>, GitLab: <project>#<pipeline_id>
@@ -34,10 +102,28 @@ pub struct NormalizedEvent {
     pub job_id: Option<String>,
     pub event_type: EventType,
     pub logs_uri: Option<String>,
+    /// Repository identity: `owner/name`, or the project path on GitLab.
+    pub repository: Option<String>,
+    /// Web base URL of the instance hosting `repository`.
+    pub instance_url: Option<String>,
+    pub commit_sha: Option<String>,
+    pub branch: Option<String>,
+    /// Pull request number, or merge request IID on GitLab.
+    pub pr_number: Option<u64>,
+    pub job_name: Option<String>,
+    pub stage: Option<String>,
+    pub attempt: Option<u32>,
+    pub actor: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Platform-specific details without a typed field.
     pub metadata: HashMap<String, String>,
     /// Raw event name/type as received (e.g., "workflow_run", "check_suite", "pipeline")
     pub raw_event_type: Option<String>,
-    /// Optional trigger info (e.g., "push", "merge_request")
+    /// What started the pipeline (e.g., "push", "merge_request"). Only
+    /// pipeline-level deliveries report it, so job events carry it only when
+    /// expanded from one.
     pub trigger_source: Option<String>,
 }
 
@@ -49,10 +135,7 @@ impl NormalizedEvent {
         event_type: EventType,
         logs_uri: Option<String>,
     ) -> Self {
-        let platform_id = match platform {
-            Platform::GitHub => format!("github#{}", pipeline_id),
-            Platform::GitLab => format!("gitlab#{}", pipeline_id),
-        };
+        let platform_id = platform.event_id(&pipeline_id);
 
         Self {
             platform,
@@ -61,9 +144,35 @@ impl NormalizedEvent {
             job_id,
             event_type,
             logs_uri,
+            repository: None,
+            instance_url: None,
+            commit_sha: None,
+            branch: None,
+            pr_number: None,
+            job_name: None,
+            stage: None,
+            attempt: None,
+            actor: None,
+            created_at: None,
+            started_at: None,
+            completed_at: None,
             metadata: HashMap::new(),
             raw_event_type: None,
             trigger_source: None,
         }
     }
+
+    /// Time spent waiting for a runner, from creation until the work started.
+    pub fn queue_time(&self) -> Option<Duration> {
+        elapsed(self.created_at?, self.started_at?)
+    }
+
+    /// Time the job or pipeline ran, from start until completion.
+    pub fn duration(&self) -> Option<Duration> {
+        elapsed(self.started_at?, self.completed_at?)
+    }
+}
+
+fn elapsed(from: DateTime<Utc>, to: DateTime<Utc>) -> Option<Duration> {
+    (to - from).to_std().ok()
 }
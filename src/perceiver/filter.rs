@@ -0,0 +1,309 @@
+use crate::errors::AppError;
+use crate::perceiver::event::NormalizedEvent;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::debug;
+
+/// Service-level include/exclude rules applied to events before they are
+/// queued, read from `EVENT_FILTER_FILE`:
+///
+/// ```yaml
+/// include:
+///   - repository: "acme/*"
+/// exclude:
+///   - name: dependabot
+///     branch: "dependabot/*"
+///   - repository: [acme/docs, acme/website]
+///   - trigger_source: schedule
+///   - actor: "*[bot]"
+///     event_type: JobStarted
+/// ```
+///
+/// A rule matches when every field it sets matches; fields take a glob or a
+/// list of globs, where `*` matches any run of characters and `?` any one.
+/// An event is kept when it matches some `include` rule (or there are none)
+/// and no `exclude` rule. Events lacking a field never match a rule on it;
+/// in particular `trigger_source` is only known for pipeline events and the
+/// jobs expanded from them, not for job-level deliveries such as GitHub's
+/// `workflow_job`.
+#[derive(Default)]
+pub struct EventFilter {
+    include: Vec<Rule>,
+    exclude: Vec<Rule>,
+    /// Events dropped, by platform and the rule that dropped them.
+    filtered: Mutex<BTreeMap<(String, String), u64>>,
+}
+
+#[derive(Deserialize)]
+struct FilterFile {
+    #[serde(default)]
+    include: Vec<RuleConfig>,
+    #[serde(default)]
+    exclude: Vec<RuleConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    name: Option<String>,
+    repository: Option<Patterns>,
+    branch: Option<Patterns>,
+    trigger_source: Option<Patterns>,
+    actor: Option<Patterns>,
+    workflow_name: Option<Patterns>,
+    event_type: Option<Patterns>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Patterns {
+    One(String),
+    Many(Vec<String>),
+}
+
+struct Rule {
+    name: String,
+    fields: Vec<(Field, Vec<Regex>)>,
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Repository,
+    Branch,
+    TriggerSource,
+    Actor,
+    WorkflowName,
+    EventType,
+}
+
+impl EventFilter {
+    /// Filter from `EVENT_FILTER_FILE`, or one keeping every event when unset.
+    pub fn from_env() -> Result<Self, AppError> {
+        match std::env::var("EVENT_FILTER_FILE") {
+            Ok(path) => Self::from_file(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, AppError> {
+        let contents = std::fs::read(path)?;
+        let file: FilterFile = serde_yaml::from_slice(&contents)
+            .map_err(|e| AppError::ConfigError(format!("Invalid event filter file {:?}: {}", path, e)))?;
+
+        let compile = |rules: Vec<RuleConfig>, kind: &str| -> Result<Vec<Rule>, AppError> {
+            rules
+                .into_iter()
+                .enumerate()
+                .map(|(index, rule)| Rule::compile(rule, format!("{}[{}]", kind, index)))
+                .collect()
+        };
+
+        Ok(Self {
+            include: compile(file.include, "include")?,
+            exclude: compile(file.exclude, "exclude")?,
+            filtered: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Drops the events the rules filter out, counting each one.
+    pub fn retain(&self, events: &mut Vec<NormalizedEvent>) {
+        events.retain(|event| match self.rejecting_rule(event) {
+            Some(rule) => {
+                debug!("Filtered out event {} by {}", event.platform_id, rule);
+                let platform = format!("{:?}", event.platform).to_lowercase();
+                *self
+                    .filtered
+                    .lock()
+                    .unwrap()
+                    .entry((platform, rule.to_string()))
+                    .or_default() += 1;
+                false
+            }
+            None => true,
+        });
+    }
+
+    /// Counts of filtered events, keyed by platform and rule name.
+    pub fn filtered_counts(&self) -> Vec<((String, String), u64)> {
+        self.filtered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect()
+    }
+
+    /// Name of the rule that filters `event` out, if any.
+    fn rejecting_rule(&self, event: &NormalizedEvent) -> Option<&str> {
+        if !self.include.is_empty() && !self.include.iter().any(|rule| rule.matches(event)) {
+            return Some("include");
+        }
+        self.exclude
+            .iter()
+            .find(|rule| rule.matches(event))
+            .map(|rule| rule.name.as_str())
+    }
+}
+
+impl Rule {
+    fn compile(config: RuleConfig, default_name: String) -> Result<Self, AppError> {
+        let name = config.name.unwrap_or(default_name);
+
+        let fields = [
+            (Field::Repository, config.repository),
+            (Field::Branch, config.branch),
+            (Field::TriggerSource, config.trigger_source),
+            (Field::Actor, config.actor),
+            (Field::WorkflowName, config.workflow_name),
+            (Field::EventType, config.event_type),
+        ]
+        .into_iter()
+        .filter_map(|(field, patterns)| patterns.map(|patterns| (field, patterns)))
+        .map(|(field, patterns)| {
+            let patterns = match patterns {
+                Patterns::One(pattern) => vec![pattern],
+                Patterns::Many(patterns) => patterns,
+            };
+            let globs = patterns
+                .iter()
+                .map(|pattern| glob(pattern))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| AppError::ConfigError(format!("Invalid pattern in filter rule {}: {}", name, e)))?;
+            Ok((field, globs))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+        if fields.is_empty() {
+            return Err(AppError::ConfigError(format!("Filter rule {} sets no fields", name)));
+        }
+
+        Ok(Self { name, fields })
+    }
+
+    fn matches(&self, event: &NormalizedEvent) -> bool {
+        self.fields.iter().all(|(field, globs)| match field.value(event) {
+            Some(value) => globs.iter().any(|glob| glob.is_match(&value)),
+            None => false,
+        })
+    }
+}
+
+impl Field {
+    fn value(&self, event: &NormalizedEvent) -> Option<String> {
+        match self {
+            Field::Repository => event.repository.clone(),
+            Field::Branch => event.branch.clone(),
+            Field::TriggerSource => event.trigger_source.clone(),
+            Field::Actor => event.actor.clone(),
+            Field::WorkflowName => event.metadata.get("workflow_name").cloned(),
+            Field::EventType => Some(format!("{:?}", event.event_type)),
+        }
+    }
+}
+
+/// Compiles a glob into an anchored regex.
+fn glob(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perceiver::event::{EventType, Platform};
+
+    fn filter(yaml: &str, name: &str) -> Result<EventFilter, AppError> {
+        let path = std::env::temp_dir().join(format!("event-filter-{}-{}.yaml", std::process::id(), name));
+        std::fs::write(&path, yaml).unwrap();
+        let filter = EventFilter::from_file(&path);
+        std::fs::remove_file(path).unwrap();
+        filter
+    }
+
+    fn event(repository: &str, branch: Option<&str>, actor: &str) -> NormalizedEvent {
+        let mut event = NormalizedEvent::new(Platform::GitHub, "1".to_string(), None, EventType::JobStarted, None);
+        event.repository = Some(repository.to_string());
+        event.branch = branch.map(str::to_string);
+        event.actor = Some(actor.to_string());
+        event
+    }
+
+    #[test]
+    fn matches_whole_values_against_globs() {
+        let pattern = glob("acme/*").unwrap();
+        assert!(pattern.is_match("acme/widgets"));
+        assert!(pattern.is_match("acme/"));
+        assert!(!pattern.is_match("other/acme/widgets"));
+
+        let pattern = glob("v?.x").unwrap();
+        assert!(pattern.is_match("v1.x"));
+        assert!(!pattern.is_match("v10.x"));
+        assert!(!pattern.is_match("v1-x"));
+
+        // Regex syntax in patterns is matched literally.
+        let pattern = glob("*[bot]").unwrap();
+        assert!(pattern.is_match("dependabot[bot]"));
+        assert!(!pattern.is_match("dependabot"));
+    }
+
+    #[test]
+    fn keeps_included_events_not_excluded() {
+        let filter = filter(
+            r#"
+include:
+  - repository: "acme/*"
+exclude:
+  - name: dependabot
+    branch: "dependabot/*"
+  - repository: [acme/docs, acme/website]
+  - actor: "*[bot]"
+    event_type: JobStarted
+"#,
+            "rules",
+        )
+        .unwrap();
+
+        let mut events = vec![
+            event("acme/widgets", Some("main"), "octocat"),
+            event("other/widgets", Some("main"), "octocat"),
+            event("acme/widgets", Some("dependabot/cargo/serde"), "octocat"),
+            event("acme/website", Some("main"), "octocat"),
+            event("acme/widgets", Some("main"), "renovate[bot]"),
+            // Without a branch the dependabot rule cannot match.
+            event("acme/gadgets", None, "octocat"),
+        ];
+        filter.retain(&mut events);
+
+        let kept: Vec<_> = events.iter().map(|e| e.repository.as_deref().unwrap()).collect();
+        assert_eq!(kept, ["acme/widgets", "acme/gadgets"]);
+
+        let counts = filter.filtered_counts();
+        let count = |rule: &str| {
+            counts
+                .iter()
+                .find(|((platform, name), _)| platform == "github" && name == rule)
+                .map(|(_, count)| *count)
+        };
+        assert_eq!(count("include"), Some(1));
+        assert_eq!(count("dependabot"), Some(1));
+        assert_eq!(count("exclude[1]"), Some(1));
+        assert_eq!(count("exclude[2]"), Some(1));
+    }
+
+    #[test]
+    fn rejects_empty_or_unknown_rule_fields() {
+        assert!(matches!(filter("exclude:\n  - name: empty\n", "empty"), Err(AppError::ConfigError(_))));
+        assert!(matches!(filter("exclude:\n  - project: acme\n", "unknown"), Err(AppError::ConfigError(_))));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::errors::AppError;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+/// Minimal Gitea / Forgejo REST client used for config loading, pull request
+/// lookup and job re-runs against a self-hosted instance.
+pub struct GiteaClient {
+    http: Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl GiteaClient {
+    pub fn new(base_url: &str, token: Option<String>) -> Self {
+        Self {
+            http: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    /// Builds a client from `GITEA_BASE_URL` and the optional `GITEA_TOKEN`.
+    pub fn from_env() -> Result<Self, AppError> {
+        let base_url = std::env::var("GITEA_BASE_URL")
+            .map_err(|_| AppError::ConfigError("Missing GITEA_BASE_URL".into()))?;
+
+        Ok(Self::new(&base_url, std::env::var("GITEA_TOKEN").ok()))
+    }
+
+    /// Fetches a file at `reference`, returning `None` when it does not exist.
+    pub async fn get_file(
+        &self,
+        repository: &str,
+        reference: &str,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        let url = format!("{}/api/v1/repos/{}/raw/{}", self.base_url, repository, path);
+        let response = self
+            .authorize(self.http.get(&url).query(&[("ref", reference)]))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Number of the pull request `commit` belongs to, if any.
+    pub async fn pull_request_for_commit(&self, repository: &str, commit: &str) -> Result<Option<u64>, AppError> {
+        let url = format!("{}/api/v1/repos/{}/commits/{}/pull", self.base_url, repository, commit);
+        let response = self.authorize(self.http.get(&url)).send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let pull: PullRequest = response.error_for_status()?.json().await?;
+        Ok(Some(pull.number))
+    }
+
+    /// Requests a re-run of a single Actions job.
+    pub async fn rerun_job(&self, repository: &str, job_id: &str) -> Result<(), AppError> {
+        let url = format!(
+            "{}/api/v1/repos/{}/actions/jobs/{}/rerun",
+            self.base_url, repository, job_id
+        );
+
+        self.authorize(self.http.post(&url))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => req.header("Authorization", format!("token {}", token)),
+            None => req,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    number: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, Query, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use axum::Router;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Re-run job IDs with the `Authorization` header they were sent with.
+    type Reruns = Vec<(String, Option<String>)>;
+
+    #[derive(Clone, Default)]
+    struct MockState {
+        reruns: Arc<Mutex<Reruns>>,
+    }
+
+    async fn raw_file(
+        Path((owner, repo, path)): Path<(String, String, String)>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Result<&'static str, StatusCode> {
+        match (owner.as_str(), repo.as_str(), path.as_str(), query.get("ref").map(String::as_str)) {
+            ("acme", "widgets", ".optimizer.yml", Some("abc123")) => {
+                Ok("allow_flaky_retry: false\nmax_job_duration: 120\n")
+            }
+            _ => Err(StatusCode::NOT_FOUND),
+        }
+    }
+
+    async fn rerun(
+        State(state): State<MockState>,
+        Path((_owner, _repo, job_id)): Path<(String, String, String)>,
+        headers: HeaderMap,
+    ) -> StatusCode {
+        if job_id != "42" {
+            return StatusCode::NOT_FOUND;
+        }
+        let auth = headers
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        state.reruns.lock().unwrap().push((job_id, auth));
+        StatusCode::OK
+    }
+
+    async fn spawn_mock() -> (String, MockState) {
+        let state = MockState::default();
+        let app = Router::new()
+            .route("/api/v1/repos/:owner/:repo/raw/*path", get(raw_file))
+            .route("/api/v1/repos/:owner/:repo/actions/jobs/:job_id/rerun", post(rerun))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}", addr), state)
+    }
+
+    #[tokio::test]
+    async fn get_file_returns_contents_at_ref() {
+        let (base_url, _) = spawn_mock().await;
+        let client = GiteaClient::new(&base_url, None);
+
+        let file = client
+            .get_file("acme/widgets", "abc123", ".optimizer.yml")
+            .await
+            .unwrap()
+            .expect("file should exist");
+
+        let config: crate::config::Config = serde_yaml::from_slice(&file).unwrap();
+        assert!(!config.allow_flaky_retry);
+        assert_eq!(config.max_job_duration, 120);
+    }
+
+    #[tokio::test]
+    async fn get_file_returns_none_when_missing() {
+        let (base_url, _) = spawn_mock().await;
+        let client = GiteaClient::new(&format!("{}/", base_url), None);
+
+        let file = client
+            .get_file("acme/widgets", "abc123", ".optimizer.json")
+            .await
+            .unwrap();
+
+        assert!(file.is_none());
+    }
+
+    #[tokio::test]
+    async fn rerun_job_posts_with_token() {
+        let (base_url, state) = spawn_mock().await;
+        let client = GiteaClient::new(&base_url, Some("s3cret".into()));
+
+        client.rerun_job("acme/widgets", "42").await.unwrap();
+
+        let reruns = state.reruns.lock().unwrap();
+        assert_eq!(reruns.as_slice(), &[("42".to_string(), Some("token s3cret".to_string()))]);
+    }
+
+    #[tokio::test]
+    async fn rerun_job_surfaces_http_errors() {
+        let (base_url, state) = spawn_mock().await;
+        let client = GiteaClient::new(&base_url, None);
+
+        assert!(client.rerun_job("acme/widgets", "7").await.is_err());
+        assert!(state.reruns.lock().unwrap().is_empty());
+    }
+}
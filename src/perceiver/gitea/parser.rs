@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use crate::errors::AppError;
+use crate::perceiver::builder::EventBuilder;
+use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
+
+#[derive(Deserialize)]
+pub struct GiteaWorkflowJobPayload {
+    pub workflow_job: GiteaWorkflowJob,
+    pub repository: GiteaRepository,
+}
+
+#[derive(Deserialize)]
+pub struct GiteaWorkflowRunPayload {
+    pub action: Option<String>,
+    pub workflow_run: GiteaWorkflowRun,
+    pub repository: GiteaRepository,
+}
+
+#[derive(Deserialize)]
+pub struct GiteaRepository {
+    pub full_name: String,
+    pub html_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct GiteaWorkflowJob {
+    pub id: u64,
+    pub run_id: u64,
+    pub run_attempt: Option<u32>,
+    pub name: Option<String>,
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub head_sha: String,
+    pub head_branch: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GiteaWorkflowRun {
+    pub id: u64,
+    pub name: Option<String>,
+    pub run_attempt: Option<u32>,
+    pub event: Option<String>,
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub head_sha: String,
+    pub head_branch: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GiteaGenericPayload {
+    repository: Option<GiteaRepository>,
+}
+
+/// Routes a delivery to the parser for its `X-Gitea-Event` name.
+///
+/// Event names without a dedicated parser become `EventType::Unknown` events.
+pub fn parse_event(event_name: &str, payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let mut event = match event_name {
+        "workflow_job" => parse_workflow_job(payload)?,
+        "workflow_run" => parse_workflow_run(payload)?,
+        _ => {
+            let payload: Option<GiteaGenericPayload> = serde_json::from_slice(payload).ok();
+            let mut builder = EventBuilder::new(Platform::Gitea, EventType::Unknown);
+            if let Some(repository) = payload.and_then(|p| p.repository) {
+                builder = with_repository(builder, &repository);
+            }
+            builder.build()?
+        }
+    };
+
+    event.raw_event_type = Some(event_name.to_string());
+    Ok(event)
+}
+
+pub fn parse_workflow_job(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GiteaWorkflowJobPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Gitea workflow_job payload: {}", e)))?;
+
+    let job = &payload.workflow_job;
+
+    let event_type = match job.status.as_str() {
+        "queued" | "waiting" | "in_progress" => EventType::JobStarted,
+        // Waiting on a job it needs, or on approval to run.
+        "blocked" => EventType::JobManual,
+        "completed" => match job.conclusion.as_deref() {
+            Some("success") => EventType::JobSucceeded,
+            Some("failure") => EventType::JobFailed,
+            Some("cancelled") => EventType::JobCancelled,
+            Some("skipped") => EventType::JobSkipped,
+            _ => EventType::Unknown,
+        },
+        _ => EventType::Unknown,
+    };
+
+    let logs_uri = format!(
+        "{}/api/v1/repos/{}/actions/jobs/{}/logs",
+        instance_url(&payload.repository),
+        payload.repository.full_name,
+        job.id
+    );
+
+    with_repository(EventBuilder::new(Platform::Gitea, event_type), &payload.repository)
+        .pipeline_id(job.run_id.to_string())
+        .job_id(job.id.to_string())
+        .logs_uri(logs_uri)
+        .commit_sha(job.head_sha.clone())
+        .branch(job.head_branch.clone())
+        .attempt(job.run_attempt)
+        .job_name(job.name.clone())
+        .build()
+}
+
+pub fn parse_workflow_run(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GiteaWorkflowRunPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Gitea workflow_run payload: {}", e)))?;
+
+    let run = &payload.workflow_run;
+
+    let completed = payload.action.as_deref() == Some("completed") || run.status == "completed";
+    let event_type = match (completed, run.conclusion.as_deref()) {
+        (true, Some("success")) | (true, Some("skipped")) => EventType::PipelineCompleted,
+        (true, Some("failure")) => EventType::PipelineErrored,
+        (true, Some("cancelled")) => EventType::PipelineCancelled,
+        (true, Some("timed_out")) => EventType::PipelineTimedOut,
+        (true, Some("action_required")) => EventType::PipelineActionRequired,
+        _ => EventType::Unknown,
+    };
+
+    with_repository(EventBuilder::new(Platform::Gitea, event_type), &payload.repository)
+        .pipeline_id(run.id.to_string())
+        .trigger_source(run.event.clone())
+        .commit_sha(run.head_sha.clone())
+        .branch(run.head_branch.clone())
+        .attempt(run.run_attempt)
+        .metadata("run_id", run.id.to_string())
+        .metadata("conclusion", run.conclusion.clone())
+        .metadata("workflow_name", run.name.clone())
+        .build()
+}
+
+fn with_repository(builder: EventBuilder, repository: &GiteaRepository) -> EventBuilder {
+    builder
+        .repository(repository.full_name.clone())
+        .instance_url(instance_url(repository))
+}
+
+/// Base URL of the Gitea instance, derived from the repository's web URL.
+fn instance_url(repository: &GiteaRepository) -> String {
+    let html_url = repository.html_url.trim_end_matches('/');
+    html_url
+        .strip_suffix(&repository.full_name)
+        .unwrap_or(html_url)
+        .trim_end_matches('/')
+        .to_string()
+}
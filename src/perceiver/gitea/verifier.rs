@@ -0,0 +1,30 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::errors::AppError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Verifies an `X-Gitea-Signature` / `X-Forgejo-Signature` header, which
+/// carries the hex HMAC-SHA256 of the body without any `sha256=` prefix.
+pub fn verify_signature(
+    payload: &[u8],
+    signature: &str,
+    secret: &str,
+) -> Result<(), AppError> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .map_err(|_| AppError::ConfigError("Invalid HMAC key".into()))?;
+
+    mac.update(payload);
+
+    let calculated_signature = hex::encode(mac.finalize().into_bytes());
+
+    // Constant-time comparison
+    if subtle::ConstantTimeEq::ct_eq(
+        calculated_signature.as_bytes(),
+        signature.trim().as_bytes(),
+    ).into() {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized)
+    }
+}
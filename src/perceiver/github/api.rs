@@ -0,0 +1,260 @@
+use crate::config::instance::{PlatformInstance, PlatformInstances};
+use crate::errors::AppError;
+use crate::perceiver::event::NormalizedEvent;
+use chrono::{DateTime, Utc};
+use reqwest::header::LOCATION;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Minimal GitHub REST client used for config loading, log downloads, job
+/// re-runs, pull request lookup and comments.
+pub struct GitHubClient {
+    http: Client,
+    /// Same as `http` but not following redirects, for signed log URLs.
+    http_without_redirects: Client,
+    api_url: String,
+    token: Option<String>,
+}
+
+impl GitHubClient {
+    pub fn new(http: Client, http_without_redirects: Client, api_url: &str, token: Option<String>) -> Self {
+        Self {
+            http,
+            http_without_redirects,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    /// Builds a client on `instance` authenticated for `repository`.
+    pub async fn for_repository(
+        instance: &PlatformInstance,
+        repository: &str,
+        installation_id: Option<u64>,
+    ) -> Result<Self, AppError> {
+        let token = instance.token_for(repository, installation_id).await?;
+        Ok(Self::new(
+            instance.http().clone(),
+            instance.http_without_redirects().clone(),
+            instance.api_url(),
+            token,
+        ))
+    }
+
+    /// Builds a client for the instance, repository and app installation
+    /// `event` was delivered for.
+    pub async fn for_event(event: &NormalizedEvent) -> Result<Self, AppError> {
+        let repository = event
+            .repository
+            .as_deref()
+            .ok_or_else(|| AppError::BadRequest("Event has no repository".into()))?;
+
+        let instance = PlatformInstances::global()?.for_event(event)?;
+        Self::for_repository(instance, repository, installation_id(event)).await
+    }
+
+    /// Fetches a file at `reference`, returning `None` when it does not exist.
+    pub async fn get_file(
+        &self,
+        repository: &str,
+        reference: &str,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        let url = format!("{}/repos/{}/contents/{}", self.api_url, repository, path);
+        let response = self
+            .authorize_as(self.http.get(&url).query(&[("ref", reference)]), "application/vnd.github.raw")
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Requests the logs of a job or workflow run from its `logs` endpoint,
+    /// returning the response to stream them from, or `None` once GitHub has
+    /// expired them.
+    ///
+    /// GitHub answers with a redirect to a short-lived signed blob URL. It is
+    /// followed here rather than by the HTTP client, so the API token is never
+    /// sent to the storage host.
+    pub async fn download_logs(&self, url: &str) -> Result<Option<Response>, AppError> {
+        let response = self.authorize(self.http_without_redirects.get(url)).send().await?;
+
+        let response = match response.status() {
+            StatusCode::GONE => return Ok(None),
+            status if status.is_redirection() => {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| response.url().join(location).ok())
+                    .ok_or_else(|| AppError::BadRequest(format!("Log redirect from {} has no valid location", url)))?;
+                self.http
+                    .get(location)
+                    .header("User-Agent", "ci-cd-optimizer")
+                    .send()
+                    .await?
+            }
+            _ => response,
+        };
+
+        Ok(Some(response.error_for_status()?))
+    }
+
+    /// Steps of an Actions job, in the order they ran.
+    pub async fn job_steps(&self, repository: &str, job_id: &str) -> Result<Vec<JobStep>, AppError> {
+        let url = format!("{}/repos/{}/actions/jobs/{}", self.api_url, repository, job_id);
+        let job: Job = self
+            .authorize(self.http.get(&url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut steps = job.steps;
+        steps.sort_by_key(|step| step.number);
+        Ok(steps)
+    }
+
+    /// Requests a re-run of a single Actions job.
+    pub async fn rerun_job(&self, repository: &str, job_id: &str) -> Result<(), AppError> {
+        let url = format!("{}/repos/{}/actions/jobs/{}/rerun", self.api_url, repository, job_id);
+
+        self.authorize(self.http.post(&url))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Number of the pull request `commit` belongs to, preferring an open one.
+    pub async fn pull_request_for_commit(&self, repository: &str, commit: &str) -> Result<Option<u64>, AppError> {
+        let url = format!("{}/repos/{}/commits/{}/pulls", self.api_url, repository, commit);
+        let response = self.authorize(self.http.get(&url)).send().await?;
+
+        // Commits GitHub cannot find yet (e.g. just pushed) have no pulls.
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let pulls: Vec<PullRequest> = response.error_for_status()?.json().await?;
+        Ok(pulls
+            .iter()
+            .find(|pr| pr.state == "open")
+            .or(pulls.first())
+            .map(|pr| pr.number))
+    }
+
+    /// Posts a comment on a pull request.
+    pub async fn create_comment(&self, repository: &str, pr_number: u64, body: &str) -> Result<(), AppError> {
+        let url = format!("{}/repos/{}/issues/{}/comments", self.api_url, repository, pr_number);
+
+        self.authorize(self.http.post(&url))
+            .json(&json!({ "body": body }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn authorize(&self, req: RequestBuilder) -> RequestBuilder {
+        self.authorize_as(req, "application/vnd.github+json")
+    }
+
+    /// Authorizes a request accepting `media_type` only; a second `Accept`
+    /// header would be sent alongside, not in place of, the first.
+    fn authorize_as(&self, req: RequestBuilder, media_type: &str) -> RequestBuilder {
+        let req = req
+            .header("Accept", media_type)
+            .header("User-Agent", "ci-cd-optimizer");
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Job {
+    #[serde(default)]
+    steps: Vec<JobStep>,
+}
+
+/// A step of an Actions job, named as in the workflow file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobStep {
+    pub name: String,
+    pub number: u32,
+    pub conclusion: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    number: u64,
+    state: String,
+}
+
+/// App installation ID from a webhook delivery, if the event came from one.
+pub fn installation_id(event: &NormalizedEvent) -> Option<u64> {
+    event.metadata.get("installation_id").and_then(|id| id.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::http::HeaderMap;
+    use axum::routing::get;
+    use axum::Router;
+
+    /// Answers with the raw file only when it is the one media type
+    /// accepted, as GitHub otherwise answers with the JSON contents envelope.
+    async fn contents(Path((owner, repo, path)): Path<(String, String, String)>, headers: HeaderMap) -> String {
+        assert_eq!((owner.as_str(), repo.as_str(), path.as_str()), ("acme", "widgets", ".optimizer.yml"));
+
+        let accept: Vec<_> = headers.get_all("Accept").iter().collect();
+        if accept == ["application/vnd.github.raw"] {
+            "allow_flaky_retry: false\nmax_job_duration: 120\n".to_string()
+        } else {
+            json!({ "type": "file", "encoding": "base64", "content": "" }).to_string()
+        }
+    }
+
+    async fn spawn_mock() -> String {
+        let app = Router::new().route("/repos/:owner/:repo/contents/*path", get(contents));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn get_file_accepts_only_the_raw_media_type() {
+        let base_url = spawn_mock().await;
+        let client = GitHubClient::new(Client::new(), Client::new(), &base_url, Some("s3cret".into()));
+
+        let file = client
+            .get_file("acme/widgets", "abc123", ".optimizer.yml")
+            .await
+            .unwrap()
+            .expect("file should exist");
+
+        let config: crate::config::Config = serde_yaml::from_slice(&file).unwrap();
+        assert!(!config.allow_flaky_retry);
+        assert_eq!(config.max_job_duration, 120);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::errors::AppError;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Installation tokens are replaced this long before GitHub expires them.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// App JWTs are backdated to tolerate clock drift between us and GitHub.
+const JWT_BACKDATE_SECS: u64 = 60;
+/// GitHub rejects app JWTs valid for longer than ten minutes.
+const JWT_LIFETIME_SECS: u64 = 9 * 60;
+
+/// Credentials for outbound GitHub API calls.
+///
+/// With a GitHub App configured, each repository gets a token for the app
+/// installation covering it, taken from the webhook's `installation.id` or
+/// looked up through the API. Installation tokens are cached until shortly
+/// before they expire. Without an app, the instance's static token is used.
+pub struct GitHubAuth {
+    http: Client,
+    api_url: String,
+    app: Option<GitHubApp>,
+    token: Option<String>,
+    installations: Mutex<HashMap<String, u64>>,
+    /// Each installation's token behind its own lock, held while the token
+    /// is refreshed so concurrent callers wait for one new token instead of
+    /// each minting their own, without holding up other installations.
+    tokens: Mutex<HashMap<u64, Arc<tokio::sync::Mutex<Option<InstallationToken>>>>>,
+}
+
+struct GitHubApp {
+    app_id: String,
+    key: EncodingKey,
+}
+
+struct InstallationToken {
+    token: String,
+    expires_at: SystemTime,
+}
+
+#[derive(Serialize)]
+struct AppClaims {
+    iat: u64,
+    exp: u64,
+    iss: String,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    token: String,
+    expires_at: String,
+}
+
+impl GitHubAuth {
+    pub fn new(http: Client, api_url: &str, app: Option<(String, EncodingKey)>, token: Option<String>) -> Self {
+        Self {
+            http,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            app: app.map(|(app_id, key)| GitHubApp { app_id, key }),
+            token,
+            installations: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Parses a GitHub App private key in PEM format.
+    pub fn app_key(pem: &[u8]) -> Result<EncodingKey, AppError> {
+        EncodingKey::from_rsa_pem(pem)
+            .map_err(|e| AppError::ConfigError(format!("Invalid GitHub App private key: {}", e)))
+    }
+
+    /// Token for calls against `repository` (`owner/name`), or `None` when no
+    /// credentials are configured.
+    pub async fn token_for(
+        &self,
+        repository: &str,
+        installation_id: Option<u64>,
+    ) -> Result<Option<String>, AppError> {
+        let Some(app) = &self.app else {
+            return Ok(self.token.clone());
+        };
+
+        if let Some(installation_id) = installation_id {
+            return self
+                .installation_token(app, installation_id)
+                .await?
+                .map(Some)
+                .ok_or_else(|| uninstalled(installation_id));
+        }
+
+        let installation_id = self.installation_for(app, repository).await?;
+        if let Some(token) = self.installation_token(app, installation_id).await? {
+            return Ok(Some(token));
+        }
+
+        // The app was reinstalled or the repository moved to another
+        // installation since it was looked up.
+        self.installations.lock().unwrap().remove(repository);
+        let installation_id = self.installation_for(app, repository).await?;
+        self.installation_token(app, installation_id)
+            .await?
+            .map(Some)
+            .ok_or_else(|| uninstalled(installation_id))
+    }
+
+    async fn installation_for(&self, app: &GitHubApp, repository: &str) -> Result<u64, AppError> {
+        if let Some(id) = self.installations.lock().unwrap().get(repository) {
+            return Ok(*id);
+        }
+
+        // Not locked during the lookup; concurrent lookups of the same
+        // repository find the same installation.
+        let installation: Installation = self
+            .http
+            .get(format!("{}/repos/{}/installation", self.api_url, repository))
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "ci-cd-optimizer")
+            .bearer_auth(app_jwt(app)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        self.installations
+            .lock()
+            .unwrap()
+            .insert(repository.to_string(), installation.id);
+        Ok(installation.id)
+    }
+
+    /// Token for `installation_id`, or `None` when the installation no longer
+    /// exists.
+    async fn installation_token(&self, app: &GitHubApp, installation_id: u64) -> Result<Option<String>, AppError> {
+        let slot = self
+            .tokens
+            .lock()
+            .unwrap()
+            .entry(installation_id)
+            .or_default()
+            .clone();
+        let mut cached = slot.lock().await;
+
+        if let Some(cached) = cached.as_ref() {
+            if cached.expires_at > SystemTime::now() + REFRESH_MARGIN {
+                return Ok(Some(cached.token.clone()));
+            }
+        }
+
+        let response = self
+            .http
+            .post(format!(
+                "{}/app/installations/{}/access_tokens",
+                self.api_url, installation_id
+            ))
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "ci-cd-optimizer")
+            .bearer_auth(app_jwt(app)?)
+            .send()
+            .await?;
+
+        if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::NOT_FOUND) {
+            *cached = None;
+            return Ok(None);
+        }
+        let access: AccessToken = response.error_for_status()?.json().await?;
+
+        let expires_at = chrono::DateTime::parse_from_rfc3339(&access.expires_at)
+            .map(|t| UNIX_EPOCH + Duration::from_secs(t.timestamp().max(0) as u64))
+            .map_err(|e| AppError::BadRequest(format!("Invalid token expiry {}: {}", access.expires_at, e)))?;
+
+        *cached = Some(InstallationToken {
+            token: access.token.clone(),
+            expires_at,
+        });
+
+        Ok(Some(access.token))
+    }
+}
+
+fn uninstalled(installation_id: u64) -> AppError {
+    AppError::ConfigError(format!("GitHub App installation {} no longer exists", installation_id))
+}
+
+/// Mints a short-lived JWT authenticating as the app itself.
+fn app_jwt(app: &GitHubApp) -> Result<String, AppError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let claims = AppClaims {
+        iat: now.saturating_sub(JWT_BACKDATE_SECS),
+        exp: now + JWT_LIFETIME_SECS,
+        iss: app.app_id.clone(),
+    };
+
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &app.key)
+        .map_err(|e| AppError::ConfigError(format!("Failed to sign GitHub App JWT: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::http::StatusCode as MockStatus;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+
+    async fn installation(Path((_owner, _repo)): Path<(String, String)>) -> Json<Value> {
+        Json(json!({ "id": 2 }))
+    }
+
+    /// Only installation 2 still exists.
+    async fn access_tokens(Path(id): Path<u64>) -> Result<Json<Value>, MockStatus> {
+        match id {
+            2 => Ok(Json(json!({ "token": "token-2", "expires_at": "2099-01-01T00:00:00Z" }))),
+            _ => Err(MockStatus::NOT_FOUND),
+        }
+    }
+
+    async fn spawn_mock() -> String {
+        let app = Router::new()
+            .route("/repos/:owner/:repo/installation", get(installation))
+            .route("/app/installations/:id/access_tokens", post(access_tokens));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn looks_up_the_installation_again_once_it_is_gone() {
+        let api_url = spawn_mock().await;
+        let key = GitHubAuth::app_key(include_bytes!("test_app_key.pem")).unwrap();
+        let auth = GitHubAuth::new(Client::new(), &api_url, Some(("1234".to_string(), key)), None);
+        auth.installations
+            .lock()
+            .unwrap()
+            .insert("acme/widgets".to_string(), 1);
+
+        let token = auth.token_for("acme/widgets", None).await.unwrap();
+
+        assert_eq!(token.as_deref(), Some("token-2"));
+        assert_eq!(auth.installations.lock().unwrap().get("acme/widgets"), Some(&2));
+        assert!(matches!(auth.token_for("acme/widgets", Some(1)).await, Err(AppError::ConfigError(_))));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod api;
+pub mod auth;
 pub mod parser;
-pub mod verifier;
\ No newline at end of file
+pub mod verifier;
@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use crate::errors::AppError;
+use crate::perceiver::builder::EventBuilder;
 use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
 
 #[derive(Deserialize)]
@@ -7,52 +9,711 @@ pub struct GitHubWorkflowJobPayload {
     #[serde(rename = "workflow_job")]
     pub workflow_job: GitHubWorkflowJob,
     pub repository: GitHubRepository,
+    pub sender: Option<GitHubUser>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubWorkflowRunPayload {
+    pub action: String,
+    #[serde(rename = "workflow_run")]
+    pub workflow_run: GitHubWorkflowRun,
+    pub repository: GitHubRepository,
+    pub sender: Option<GitHubUser>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubPushPayload {
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub after: String,
+    pub repository: GitHubRepository,
+    pub pusher: Option<GitHubPusher>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubPusher {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubUser {
+    pub login: String,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubPullRequestPayload {
+    pub action: String,
+    pub number: u64,
+    pub pull_request: GitHubPullRequest,
+    pub repository: GitHubRepository,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubPullRequest {
+    pub head: GitHubPullRequestRef,
+    pub base: GitHubPullRequestRef,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubPullRequestRef {
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub sha: String,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubCheckSuitePayload {
+    pub action: String,
+    pub check_suite: GitHubCheckSuite,
+    pub repository: GitHubRepository,
+    pub sender: Option<GitHubUser>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubCheckRunPayload {
+    pub action: String,
+    pub check_run: GitHubCheckRun,
+    pub repository: GitHubRepository,
+    pub sender: Option<GitHubUser>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubCheckSuite {
+    pub id: u64,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub status: Option<String>,
+    pub conclusion: Option<String>,
+    pub app: Option<GitHubApp>,
+    #[serde(default)]
+    pub pull_requests: Vec<GitHubPullRequestLink>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubCheckRun {
+    pub id: u64,
+    pub name: String,
+    pub head_sha: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub html_url: Option<String>,
+    pub details_url: Option<String>,
+    pub output: Option<GitHubCheckRunOutput>,
+    pub check_suite: Option<GitHubCheckSuiteRef>,
+    pub app: Option<GitHubApp>,
+    #[serde(default)]
+    pub pull_requests: Vec<GitHubPullRequestLink>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubCheckSuiteRef {
+    pub id: u64,
+    pub head_branch: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubCheckRunOutput {
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub annotations_count: u64,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubApp {
+    pub slug: Option<String>,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubPullRequestLink {
+    pub number: u64,
+}
+
+/// Fields common to every GitHub delivery, used for events we do not parse.
+#[derive(Deserialize)]
+pub struct GitHubGenericPayload {
+    pub action: Option<String>,
+    pub repository: Option<GitHubRepository>,
 }
 
 #[derive(Deserialize)]
 pub struct GitHubRepository {
     pub full_name: String,
+    pub html_url: Option<String>,
+}
+
+/// The GitHub App installation a delivery was sent for, present on every
+/// event delivered to an app.
+#[derive(Deserialize)]
+pub struct GitHubInstallationPayload {
+    pub installation: Option<GitHubInstallation>,
+}
+
+#[derive(Deserialize)]
+pub struct GitHubInstallation {
+    pub id: u64,
 }
 
 #[derive(Deserialize)]
 pub struct GitHubWorkflowJob {
-    pub id: String,
-    pub run_id: String,
+    pub id: u64,
+    pub run_id: u64,
     pub run_attempt: Option<u32>,
+    pub name: Option<String>,
     pub status: String,
     pub conclusion: Option<String>,
-    pub logs_url: String,
+    /// API URL of the job; its logs are served from `{url}/logs`.
+    pub url: Option<String>,
+    pub logs_url: Option<String>,
     pub head_sha: String,
+    pub head_branch: Option<String>,
+    pub workflow_name: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
 }
 
-pub fn parse_payload(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+#[derive(Deserialize)]
+pub struct GitHubWorkflowRun {
+    pub id: u64,
+    pub name: Option<String>,
+    pub run_attempt: Option<u32>,
+    pub run_number: Option<u64>,
+    pub event: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub logs_url: String,
+    /// Who started this attempt; differs from `actor` on re-runs.
+    pub triggering_actor: Option<GitHubUser>,
+    pub actor: Option<GitHubUser>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub run_started_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub pull_requests: Vec<GitHubPullRequestLink>,
+}
+
+/// Routes a delivery to the parser for its `X-GitHub-Event` name.
+///
+/// Returns `Ok(None)` for deliveries that need no processing (e.g. `ping`).
+/// Event names without a dedicated parser become `EventType::Unknown` events
+/// instead of errors so GitHub does not mark the hook as failing.
+pub fn parse_event(event_name: &str, payload: &[u8]) -> Result<Option<NormalizedEvent>, AppError> {
+    let mut event = match event_name {
+        "ping" => return Ok(None),
+        "workflow_job" => parse_workflow_job(payload)?,
+        "workflow_run" => parse_workflow_run(payload)?,
+        "push" => parse_push(payload)?,
+        "pull_request" => parse_pull_request(payload)?,
+        "check_suite" => parse_check_suite(payload)?,
+        "check_run" => parse_check_run(payload)?,
+        _ => parse_unknown(payload)?,
+    };
+
+    event.raw_event_type = Some(event_name.to_string());
+
+    if let Ok(GitHubInstallationPayload { installation: Some(installation) }) = serde_json::from_slice(payload) {
+        event.metadata.insert("installation_id".into(), installation.id.to_string());
+    }
+
+    Ok(Some(event))
+}
+
+pub fn parse_workflow_job(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
     let payload: GitHubWorkflowJobPayload = serde_json::from_slice(payload)
         .map_err(|e| AppError::BadRequest(format!("Invalid payload: {}", e)))?;
 
-    let event_type = match payload.workflow_job.status.as_str() {
+    let job = &payload.workflow_job;
+
+    let event_type = match job.status.as_str() {
+        "queued" | "in_progress" | "pending" | "requested" => EventType::JobStarted,
+        // Held by an environment's deployment protection rules.
+        "waiting" => EventType::JobManual,
+        "completed" => job_conclusion(job.conclusion.as_deref()),
+        _ => EventType::Unknown,
+    };
+
+    let logs_uri = job
+        .logs_url
+        .clone()
+        .or_else(|| job.url.as_ref().map(|url| format!("{}/logs", url)));
+
+    with_repository(EventBuilder::new(Platform::GitHub, event_type), &payload.repository)
+        .pipeline_id(job.run_id.to_string())
+        .job_id(job.id.to_string())
+        .logs_uri(logs_uri)
+        .commit_sha(job.head_sha.clone())
+        .branch(job.head_branch.clone())
+        .attempt(job.run_attempt)
+        .job_name(job.name.clone())
+        .created_at(job.created_at)
+        .started_at(job.started_at)
+        .completed_at(job.completed_at)
+        .actor(login(payload.sender.as_ref()))
+        .metadata("workflow_name", job.workflow_name.clone())
+        .build()
+}
+
+pub fn parse_workflow_run(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GitHubWorkflowRunPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid workflow_run payload: {}", e)))?;
+
+    let run = &payload.workflow_run;
+
+    // Only a finished run carries a meaningful conclusion; `requested` and
+    // `in_progress` deliveries are surfaced but not classified.
+    let event_type = match (payload.action.as_str(), run.status.as_str()) {
+        ("completed", _) | (_, "completed") => run_conclusion(run.conclusion.as_deref()),
+        _ => EventType::Unknown,
+    };
+
+    // Runs have no completion timestamp; the last update of a completed run
+    // is when it finished.
+    let completed_at = match run.status.as_str() {
+        "completed" => run.updated_at,
+        _ => None,
+    };
+
+    let builder = with_repository(EventBuilder::new(Platform::GitHub, event_type), &payload.repository)
+        .pipeline_id(run.id.to_string())
+        .logs_uri(run.logs_url.clone())
+        .raw_event_type("workflow_run".to_string())
+        .trigger_source(run.event.clone())
+        .commit_sha(run.head_sha.clone())
+        .branch(run.head_branch.clone())
+        .attempt(run.run_attempt)
+        .created_at(run.created_at)
+        .started_at(run.run_started_at)
+        .completed_at(completed_at)
+        .actor(login(
+            run.triggering_actor
+                .as_ref()
+                .or(run.actor.as_ref())
+                .or(payload.sender.as_ref()),
+        ))
+        .metadata("run_id", run.id.to_string())
+        .metadata("run_number", run.run_number.map(|n| n.to_string()))
+        .metadata("conclusion", run.conclusion.clone())
+        .metadata("workflow_name", run.name.clone());
+
+    with_pull_requests(builder, &run.pull_requests).build()
+}
+
+pub fn parse_push(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GitHubPushPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid push payload: {}", e)))?;
+
+    with_repository(EventBuilder::new(Platform::GitHub, EventType::Unknown), &payload.repository)
+        .pipeline_id(payload.after.clone())
+        .trigger_source("push".to_string())
+        .commit_sha(payload.after.clone())
+        .branch(payload.git_ref.strip_prefix("refs/heads/").map(str::to_string))
+        .actor(payload.pusher.as_ref().map(|pusher| pusher.name.clone()))
+        .metadata("ref", payload.git_ref.clone())
+        .build()
+}
+
+pub fn parse_pull_request(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GitHubPullRequestPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid pull_request payload: {}", e)))?;
+
+    let head = &payload.pull_request.head;
+
+    with_repository(EventBuilder::new(Platform::GitHub, EventType::Unknown), &payload.repository)
+        .pipeline_id(head.sha.clone())
+        .trigger_source("pull_request".to_string())
+        .commit_sha(head.sha.clone())
+        .branch(head.git_ref.clone())
+        .pr_number(payload.number)
+        .metadata("base_branch", payload.pull_request.base.git_ref.clone())
+        .metadata("action", payload.action.clone())
+        .build()
+}
+
+pub fn parse_check_suite(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GitHubCheckSuitePayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid check_suite payload: {}", e)))?;
+
+    let suite = &payload.check_suite;
+
+    let event_type = match (payload.action.as_str(), suite.conclusion.as_deref()) {
+        _ if is_actions_app(suite.app.as_ref()) => EventType::Unknown,
+        ("completed", Some("failure")) => EventType::TestFailure,
+        ("completed", conclusion) => run_conclusion(conclusion),
+        _ => EventType::Unknown,
+    };
+
+    let builder = with_repository(EventBuilder::new(Platform::GitHub, event_type), &payload.repository)
+        .pipeline_id(suite.id.to_string())
+        .commit_sha(suite.head_sha.clone())
+        .branch(suite.head_branch.clone())
+        .actor(login(payload.sender.as_ref()))
+        .metadata("check_suite_id", suite.id.to_string())
+        .metadata("conclusion", suite.conclusion.clone());
+
+    with_pull_requests(with_check_app(builder, suite.app.as_ref()), &suite.pull_requests).build()
+}
+
+pub fn parse_check_run(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GitHubCheckRunPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid check_run payload: {}", e)))?;
+
+    let run = &payload.check_run;
+
+    let event_type = match run.status.as_str() {
+        _ if is_actions_app(run.app.as_ref()) => EventType::Unknown,
         "queued" | "in_progress" => EventType::JobStarted,
-        "completed" => match payload.workflow_job.conclusion.as_deref() {
-            Some("success") => EventType::JobSucceeded,
-            Some("failure") => EventType::JobFailed,
-            Some("cancelled") => EventType::JobFailed,
-            _ => EventType::JobFailed,
+        "completed" => match run.conclusion.as_deref() {
+            Some("failure") => EventType::TestFailure,
+            conclusion => job_conclusion(conclusion),
         },
-        _ => EventType::JobFailed,
+        _ => EventType::Unknown,
     };
 
-    let mut event = NormalizedEvent::new(
-        Platform::GitHub,
-        payload.workflow_job.run_id.clone(),
-        Some(payload.workflow_job.id.clone()),
-        event_type,
-        Some(payload.workflow_job.logs_url.clone()),
-    );
+    // A check run always belongs to a suite; fall back to the run id so the
+    // event still has a stable pipeline identifier.
+    let pipeline_id = run
+        .check_suite
+        .as_ref()
+        .map(|suite| suite.id.to_string())
+        .unwrap_or_else(|| run.id.to_string());
+
+    let output = run.output.as_ref();
+
+    let builder = with_repository(EventBuilder::new(Platform::GitHub, event_type), &payload.repository)
+        .pipeline_id(pipeline_id)
+        .job_id(run.id.to_string())
+        .commit_sha(run.head_sha.clone())
+        .job_name(run.name.clone())
+        .branch(run.check_suite.as_ref().and_then(|s| s.head_branch.clone()))
+        .actor(login(payload.sender.as_ref()))
+        .metadata("action", payload.action.clone())
+        .metadata("conclusion", run.conclusion.clone())
+        .metadata("details_url", run.details_url.clone().or(run.html_url.clone()))
+        .metadata("output_title", output.and_then(|o| o.title.clone()))
+        .metadata("output_summary", output.and_then(|o| o.summary.clone()))
+        .metadata("annotations_count", output.map(|o| o.annotations_count.to_string()));
+
+    with_pull_requests(with_check_app(builder, run.app.as_ref()), &run.pull_requests).build()
+}
+
+/// Classifies the conclusion of a finished job or check run. Conclusions
+/// GitHub may add later are left unclassified rather than treated as failures.
+fn job_conclusion(conclusion: Option<&str>) -> EventType {
+    match conclusion {
+        Some("success") | Some("neutral") => EventType::JobSucceeded,
+        Some("failure") => EventType::JobFailed,
+        Some("cancelled") => EventType::JobCancelled,
+        Some("skipped") => EventType::JobSkipped,
+        Some("timed_out") => EventType::JobTimedOut,
+        Some("action_required") => EventType::JobActionRequired,
+        _ => EventType::Unknown,
+    }
+}
 
-    event.metadata.insert("repository".into(), payload.repository.full_name.clone());
-    event.metadata.insert("commit_sha".into(), payload.workflow_job.head_sha.clone());
-    if let Some(attempt) = payload.workflow_job.run_attempt {
-        event.metadata.insert("run_attempt".into(), attempt.to_string());
+/// Event type of a finished workflow run or check suite.
+fn run_conclusion(conclusion: Option<&str>) -> EventType {
+    match conclusion {
+        Some("success") | Some("neutral") | Some("skipped") => EventType::PipelineCompleted,
+        Some("failure") | Some("startup_failure") => EventType::PipelineErrored,
+        Some("cancelled") => EventType::PipelineCancelled,
+        Some("timed_out") => EventType::PipelineTimedOut,
+        Some("action_required") => EventType::PipelineActionRequired,
+        _ => EventType::Unknown,
     }
+}
+
+fn login(user: Option<&GitHubUser>) -> Option<String> {
+    user.map(|user| user.login.clone())
+}
+
+/// Whether a check was created by GitHub Actions, whose runs and jobs are
+/// already reported through `workflow_run` and `workflow_job` deliveries.
+fn is_actions_app(app: Option<&GitHubApp>) -> bool {
+    app.and_then(|app| app.slug.as_deref()) == Some("github-actions")
+}
+
+fn with_check_app(builder: EventBuilder, app: Option<&GitHubApp>) -> EventBuilder {
+    builder.metadata(
+        "check_app",
+        app.map(|app| app.slug.clone().unwrap_or_else(|| app.name.clone())),
+    )
+}
+
+/// Records the first linked pull request as the event's PR, keeping the full
+/// list in `pr_numbers` metadata.
+fn with_pull_requests(builder: EventBuilder, pull_requests: &[GitHubPullRequestLink]) -> EventBuilder {
+    if pull_requests.is_empty() {
+        return builder;
+    }
+
+    let numbers: Vec<String> = pull_requests.iter().map(|pr| pr.number.to_string()).collect();
+    builder
+        .pr_number(pull_requests[0].number)
+        .metadata("pr_numbers", numbers.join(","))
+}
+
+fn parse_unknown(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    // Unrecognised deliveries are still acknowledged; a body we cannot even
+    // read as JSON simply yields an event with no metadata.
+    let payload: Option<GitHubGenericPayload> = serde_json::from_slice(payload).ok();
+
+    let mut builder = EventBuilder::new(Platform::GitHub, EventType::Unknown);
+
+    if let Some(payload) = payload {
+        if let Some(repository) = &payload.repository {
+            builder = with_repository(builder, repository);
+        }
+        builder = builder.metadata("action", payload.action);
+    }
+
+    builder.build()
+}
+
+fn with_repository(builder: EventBuilder, repository: &GitHubRepository) -> EventBuilder {
+    builder
+        .repository(repository.full_name.clone())
+        .instance_url(instance_url(repository))
+}
+
+/// Base URL of the GitHub instance (GitHub.com or a GitHub Enterprise
+/// Server host), derived from the repository's web URL.
+fn instance_url(repository: &GitHubRepository) -> Option<String> {
+    let html_url = repository.html_url.as_deref()?.trim_end_matches('/');
+    html_url
+        .strip_suffix(&repository.full_name)
+        .map(|base| base.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const WORKFLOW_RUN: &[u8] = include_bytes!("fixtures/workflow_run.json");
+    const CHECK_SUITE: &[u8] = include_bytes!("fixtures/check_suite.json");
+    const CHECK_RUN: &[u8] = include_bytes!("fixtures/check_run.json");
 
-    Ok(event)
-}
\ No newline at end of file
+    fn at(time: &str) -> Option<DateTime<Utc>> {
+        Some(time.parse().unwrap())
+    }
+
+    /// `fixture` with the fields of `changes` overwritten at `path`.
+    fn edit(fixture: &[u8], path: &str, changes: Value) -> Vec<u8> {
+        let mut payload: Value = serde_json::from_slice(fixture).unwrap();
+        let target = payload.pointer_mut(path).unwrap();
+        for (key, value) in changes.as_object().unwrap() {
+            target[key] = value.clone();
+        }
+        serde_json::to_vec(&payload).unwrap()
+    }
+
+    #[test]
+    fn parses_completed_workflow_runs() {
+        let event = parse_workflow_run(WORKFLOW_RUN).unwrap();
+
+        assert_eq!(event.event_type, EventType::PipelineErrored);
+        assert_eq!(event.pipeline_id, "30433642");
+        assert_eq!(event.job_id, None);
+        assert_eq!(event.attempt, Some(2));
+        assert_eq!(event.repository.as_deref(), Some("acme/widgets"));
+        assert_eq!(event.instance_url.as_deref(), Some("https://github.com"));
+        assert_eq!(
+            event.logs_uri.as_deref(),
+            Some("https://api.github.com/repos/acme/widgets/actions/runs/30433642/logs")
+        );
+        assert_eq!(event.commit_sha.as_deref(), Some("acb5820ced9479c074f688cc328bf03f341a511d"));
+        assert_eq!(event.branch.as_deref(), Some("feature/widgets"));
+        assert_eq!(event.trigger_source.as_deref(), Some("pull_request"));
+        assert_eq!(event.actor.as_deref(), Some("maintainer"));
+        assert_eq!(event.created_at, at("2024-05-01T10:00:00Z"));
+        assert_eq!(event.started_at, at("2024-05-01T10:05:00Z"));
+        assert_eq!(event.completed_at, at("2024-05-01T10:12:00Z"));
+        assert_eq!(event.pr_number, Some(7));
+        assert_eq!(event.metadata.get("pr_numbers").map(String::as_str), Some("7,9"));
+        assert_eq!(event.metadata.get("run_number").map(String::as_str), Some("562"));
+        assert_eq!(event.metadata.get("workflow_name").map(String::as_str), Some("CI"));
+    }
+
+    #[test]
+    fn maps_workflow_run_conclusions() {
+        let cases = [
+            ("success", EventType::PipelineCompleted),
+            ("neutral", EventType::PipelineCompleted),
+            ("skipped", EventType::PipelineCompleted),
+            ("failure", EventType::PipelineErrored),
+            ("startup_failure", EventType::PipelineErrored),
+            ("cancelled", EventType::PipelineCancelled),
+            ("timed_out", EventType::PipelineTimedOut),
+            ("action_required", EventType::PipelineActionRequired),
+            ("stale", EventType::Unknown),
+        ];
+
+        for (conclusion, expected) in cases {
+            let payload = edit(WORKFLOW_RUN, "/workflow_run", json!({ "conclusion": conclusion }));
+            assert_eq!(parse_workflow_run(&payload).unwrap().event_type, expected, "{}", conclusion);
+        }
+    }
+
+    #[test]
+    fn leaves_unfinished_workflow_runs_unclassified() {
+        let payload = edit(WORKFLOW_RUN, "", json!({ "action": "in_progress" }));
+        let payload = edit(&payload, "/workflow_run", json!({ "status": "in_progress", "conclusion": null }));
+        let event = parse_workflow_run(&payload).unwrap();
+
+        assert_eq!(event.event_type, EventType::Unknown);
+        assert_eq!(event.completed_at, None);
+        assert_eq!(event.started_at, at("2024-05-01T10:05:00Z"));
+    }
+
+    #[test]
+    fn credits_re_runs_to_whoever_started_them() {
+        let payload = edit(WORKFLOW_RUN, "/workflow_run", json!({ "triggering_actor": null }));
+        assert_eq!(parse_workflow_run(&payload).unwrap().actor.as_deref(), Some("octocat"));
+    }
+
+    #[test]
+    fn ignores_pings() {
+        let payload = json!({ "zen": "Keep it logically awesome.", "hook_id": 1 });
+        assert!(parse_event("ping", payload.to_string().as_bytes()).unwrap().is_none());
+    }
+
+    #[test]
+    fn dispatches_on_the_event_header() {
+        let event = parse_event("workflow_run", WORKFLOW_RUN).unwrap().unwrap();
+        assert_eq!(event.event_type, EventType::PipelineErrored);
+        assert_eq!(event.raw_event_type.as_deref(), Some("workflow_run"));
+        assert_eq!(event.metadata.get("installation_id").map(String::as_str), Some("4242"));
+
+        let push = json!({
+            "ref": "refs/heads/main",
+            "after": "acb5820ced9479c074f688cc328bf03f341a511d",
+            "repository": { "full_name": "acme/widgets", "html_url": "https://github.com/acme/widgets" },
+            "pusher": { "name": "octocat" }
+        });
+        let event = parse_event("push", push.to_string().as_bytes()).unwrap().unwrap();
+        assert_eq!(event.raw_event_type.as_deref(), Some("push"));
+        assert_eq!(event.branch.as_deref(), Some("main"));
+        assert_eq!(event.actor.as_deref(), Some("octocat"));
+        assert!(!event.metadata.contains_key("installation_id"));
+    }
+
+    #[test]
+    fn keeps_unknown_events_as_unknown() {
+        let payload = json!({
+            "action": "created",
+            "repository": { "full_name": "acme/widgets", "html_url": "https://github.com/acme/widgets" },
+            "installation": { "id": 4242 }
+        });
+        let event = parse_event("star", payload.to_string().as_bytes()).unwrap().unwrap();
+
+        assert_eq!(event.event_type, EventType::Unknown);
+        assert_eq!(event.raw_event_type.as_deref(), Some("star"));
+        assert_eq!(event.repository.as_deref(), Some("acme/widgets"));
+        assert_eq!(event.metadata.get("action").map(String::as_str), Some("created"));
+        assert_eq!(event.metadata.get("installation_id").map(String::as_str), Some("4242"));
+
+        let event = parse_event("star", b"not json").unwrap().unwrap();
+        assert_eq!(event.event_type, EventType::Unknown);
+        assert_eq!(event.repository, None);
+    }
+
+    #[test]
+    fn rejects_malformed_known_events() {
+        assert!(matches!(parse_event("workflow_run", b"{}"), Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn parses_failed_check_suites_as_test_failures() {
+        let event = parse_event("check_suite", CHECK_SUITE).unwrap().unwrap();
+
+        assert_eq!(event.event_type, EventType::TestFailure);
+        assert_eq!(event.pipeline_id, "118578147");
+        assert_eq!(event.branch.as_deref(), Some("feature/widgets"));
+        assert_eq!(event.actor.as_deref(), Some("octocat"));
+        assert_eq!(event.pr_number, Some(7));
+        assert_eq!(event.metadata.get("check_app").map(String::as_str), Some("circleci-checks"));
+        assert_eq!(event.metadata.get("installation_id").map(String::as_str), Some("4242"));
+    }
+
+    #[test]
+    fn maps_check_suite_conclusions() {
+        let cases = [
+            ("success", EventType::PipelineCompleted),
+            ("cancelled", EventType::PipelineCancelled),
+            ("timed_out", EventType::PipelineTimedOut),
+            ("action_required", EventType::PipelineActionRequired),
+            ("stale", EventType::Unknown),
+        ];
+
+        for (conclusion, expected) in cases {
+            let payload = edit(CHECK_SUITE, "/check_suite", json!({ "conclusion": conclusion }));
+            assert_eq!(parse_check_suite(&payload).unwrap().event_type, expected, "{}", conclusion);
+        }
+
+        let payload = edit(CHECK_SUITE, "", json!({ "action": "requested" }));
+        assert_eq!(parse_check_suite(&payload).unwrap().event_type, EventType::Unknown);
+    }
+
+    #[test]
+    fn parses_failed_check_runs_as_test_failures() {
+        let event = parse_event("check_run", CHECK_RUN).unwrap().unwrap();
+
+        assert_eq!(event.event_type, EventType::TestFailure);
+        assert_eq!(event.pipeline_id, "118578147");
+        assert_eq!(event.job_id.as_deref(), Some("4"));
+        assert_eq!(event.job_name.as_deref(), Some("unit-tests"));
+        assert_eq!(event.branch.as_deref(), Some("feature/widgets"));
+        assert_eq!(event.pr_number, Some(7));
+        assert_eq!(event.metadata.get("pr_numbers").map(String::as_str), Some("7,9"));
+        assert_eq!(event.metadata.get("check_app").map(String::as_str), Some("circleci-checks"));
+        assert_eq!(
+            event.metadata.get("details_url").map(String::as_str),
+            Some("https://app.circleci.com/pipelines/github/acme/widgets/12")
+        );
+        assert_eq!(event.metadata.get("output_title").map(String::as_str), Some("3 tests failed"));
+        assert_eq!(event.metadata.get("output_summary").map(String::as_str), Some("widgets::render failed"));
+        assert_eq!(event.metadata.get("annotations_count").map(String::as_str), Some("3"));
+    }
+
+    #[test]
+    fn maps_check_run_statuses() {
+        let cases = [
+            (json!({ "status": "queued", "conclusion": null }), EventType::JobStarted),
+            (json!({ "status": "in_progress", "conclusion": null }), EventType::JobStarted),
+            (json!({ "conclusion": "success" }), EventType::JobSucceeded),
+            (json!({ "conclusion": "cancelled" }), EventType::JobCancelled),
+            (json!({ "conclusion": "timed_out" }), EventType::JobTimedOut),
+            (json!({ "conclusion": "stale" }), EventType::Unknown),
+        ];
+
+        for (changes, expected) in cases {
+            let payload = edit(CHECK_RUN, "/check_run", changes.clone());
+            assert_eq!(parse_check_run(&payload).unwrap().event_type, expected, "{}", changes);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_check_run_id_without_a_suite() {
+        let payload = edit(CHECK_RUN, "/check_run", json!({ "check_suite": null }));
+        let event = parse_check_run(&payload).unwrap();
+
+        assert_eq!(event.pipeline_id, "4");
+        assert_eq!(event.branch, None);
+    }
+
+    #[test]
+    fn leaves_github_actions_checks_to_workflow_events() {
+        let actions = json!({ "app": { "slug": "github-actions", "name": "GitHub Actions" } });
+
+        let suite = parse_check_suite(&edit(CHECK_SUITE, "/check_suite", actions.clone())).unwrap();
+        assert_eq!(suite.event_type, EventType::Unknown);
+        assert_eq!(suite.metadata.get("check_app").map(String::as_str), Some("github-actions"));
+
+        let run = parse_check_run(&edit(CHECK_RUN, "/check_run", actions)).unwrap();
+        assert_eq!(run.event_type, EventType::Unknown);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::config::instance::PlatformInstances;
+use crate::errors::AppError;
+use crate::perceiver::event::NormalizedEvent;
+use reqwest::header::{HeaderMap, CONTENT_RANGE, RANGE};
+use bytes::Bytes;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Bytes requested per ranged trace request.
+const TRACE_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Minimal GitLab REST client used for config loading, merge request
+/// lookup and notes against GitLab.com or a self-managed instance.
+pub struct GitLabClient {
+    http: Client,
+    api_url: String,
+    token: Option<String>,
+}
+
+impl GitLabClient {
+    pub fn new(http: Client, api_url: &str, token: Option<String>) -> Self {
+        Self {
+            http,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    /// Builds a client for the instance `event` was delivered from.
+    pub async fn for_event(event: &NormalizedEvent) -> Result<Self, AppError> {
+        let project = event
+            .repository
+            .as_deref()
+            .ok_or_else(|| AppError::BadRequest("Event has no GitLab project".into()))?;
+
+        let instance = PlatformInstances::global()?.for_event(event)?;
+        let token = instance.token_for(project, None).await?;
+        Ok(Self::new(instance.http().clone(), instance.api_url(), token))
+    }
+
+    /// Fetches a file at `reference`, returning `None` when it does not exist.
+    pub async fn get_file(
+        &self,
+        project: &str,
+        reference: &str,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        // File paths are a single, percent-encoded segment too.
+        let url = self.project_url(project, &["repository", "files", path, "raw"])?;
+
+        let response = self
+            .authorize(self.http.get(url).query(&[("ref", reference)]))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// IID of the merge request `commit` belongs to, preferring an open one.
+    pub async fn merge_request_for_commit(&self, project: &str, commit: &str) -> Result<Option<u64>, AppError> {
+        let url = self.project_url(project, &["repository", "commits", commit, "merge_requests"])?;
+        let response = self.authorize(self.http.get(url)).send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let merge_requests: Vec<MergeRequest> = response.error_for_status()?.json().await?;
+        Ok(merge_requests
+            .iter()
+            .find(|mr| mr.state == "opened")
+            .or(merge_requests.first())
+            .map(|mr| mr.iid))
+    }
+
+    /// Adds a note (comment) to merge request `iid`.
+    pub async fn create_merge_request_note(&self, project: &str, iid: u64, body: &str) -> Result<(), AppError> {
+        let url = self.project_url(project, &["merge_requests", &iid.to_string(), "notes"])?;
+
+        self.authorize(self.http.post(url))
+            .json(&json!({ "body": body }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Reader over a job's raw trace, which requests it in ranged chunks as
+    /// it is consumed so large traces are never held whole.
+    pub fn job_trace(self, project: &str, job_id: &str) -> Result<TraceReader, AppError> {
+        let url = self.project_url(project, &["jobs", job_id, "trace"])?;
+        Ok(TraceReader {
+            client: self,
+            url,
+            offset: 0,
+            done: false,
+            unranged: None,
+        })
+    }
+
+    /// API URL for `segments` under `project`, percent-encoding each segment
+    /// so namespaced project paths stay a single segment.
+    fn project_url(&self, project: &str, segments: &[&str]) -> Result<Url, AppError> {
+        let mut url = Url::parse(&self.api_url)
+            .map_err(|e| AppError::ConfigError(format!("Invalid GitLab API URL {}: {}", self.api_url, e)))?;
+        url.path_segments_mut()
+            .map_err(|_| AppError::ConfigError(format!("Invalid GitLab API URL {}", self.api_url)))?
+            .extend(["projects", project])
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn authorize(&self, req: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => req.header("PRIVATE-TOKEN", token),
+            None => req,
+        }
+    }
+}
+
+/// Streams a job trace from `GitLabClient::job_trace`.
+pub struct TraceReader {
+    client: GitLabClient,
+    url: Url,
+    /// Bytes of the trace returned so far.
+    offset: usize,
+    done: bool,
+    /// Response of a server that ignored `Range` and sent the whole trace,
+    /// with the count of already returned bytes still to skip in it.
+    unranged: Option<(Response, usize)>,
+}
+
+impl TraceReader {
+    /// Next chunk of the trace, or `None` once it has all been read.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, AppError> {
+        if let Some((response, skip)) = &mut self.unranged {
+            while let Some(mut chunk) = response.chunk().await? {
+                let skipped = (*skip).min(chunk.len());
+                *skip -= skipped;
+                let chunk = chunk.split_off(skipped);
+                if !chunk.is_empty() {
+                    self.offset += chunk.len();
+                    return Ok(Some(chunk));
+                }
+            }
+            return Ok(None);
+        }
+        if self.done {
+            return Ok(None);
+        }
+
+        let range = format!("bytes={}-{}", self.offset, self.offset + TRACE_CHUNK_BYTES - 1);
+        let response = self
+            .client
+            .authorize(self.client.http.get(self.url.clone()))
+            .header(RANGE, range)
+            .send()
+            .await?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let total = content_range_total(response.headers());
+                let chunk = response.bytes().await?;
+                self.offset += chunk.len();
+                self.done = match total {
+                    Some(total) => self.offset >= total,
+                    // Running jobs report an unknown length (`*`).
+                    None => chunk.len() < TRACE_CHUNK_BYTES,
+                } || chunk.is_empty();
+                Ok((!chunk.is_empty()).then_some(chunk))
+            }
+            // Nothing from the offset on: the previous chunk ended exactly
+            // at the end of the trace, or the trace is empty.
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                self.done = true;
+                Ok(None)
+            }
+            _ => {
+                self.unranged = Some((response.error_for_status()?, self.offset));
+                Box::pin(self.chunk()).await
+            }
+        }
+    }
+}
+
+/// Total length from a `Content-Range: bytes a-b/total` header, when known.
+fn content_range_total(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    iid: u64,
+    state: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, State};
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    /// Notes posted, as project, merge request IID, token and body.
+    type Notes = Arc<Mutex<Vec<(String, u64, Option<String>, Value)>>>;
+
+    async fn note(
+        State(notes): State<Notes>,
+        Path((project, iid)): Path<(String, u64)>,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> axum::http::StatusCode {
+        let token = headers
+            .get("PRIVATE-TOKEN")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        notes.lock().unwrap().push((project, iid, token, body));
+        axum::http::StatusCode::CREATED
+    }
+
+    #[tokio::test]
+    async fn posts_merge_request_notes() {
+        let notes = Notes::default();
+        let app = Router::new()
+            .route("/api/v4/projects/:project/merge_requests/:iid/notes", post(note))
+            .with_state(notes.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = GitLabClient::new(
+            Client::new(),
+            &format!("http://{}/api/v4/", addr),
+            Some("glpat-secret".to_string()),
+        );
+        client
+            .create_merge_request_note("acme/widgets", 7, "Retried the failed job.")
+            .await
+            .unwrap();
+
+        let notes = notes.lock().unwrap();
+        let [(project, iid, token, body)] = notes.as_slice() else {
+            panic!("expected one note, got {}", notes.len());
+        };
+        assert_eq!(project, "acme/widgets");
+        assert_eq!(*iid, 7);
+        assert_eq!(token.as_deref(), Some("glpat-secret"));
+        assert_eq!(body["body"], "Retried the failed job.");
+    }
+}
@@ -1,2 +1,3 @@
+pub mod api;
 pub mod parser;
-pub mod verifier;
\ No newline at end of file
+pub mod verifier;
@@ -1,13 +1,40 @@
-use serde::Deserialize;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer};
 use crate::errors::AppError;
+use crate::perceiver::builder::EventBuilder;
 use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
 
+/// Flat document sent by GitLab's Job Hook (`object_kind: "build"`).
 #[derive(Deserialize)]
 pub struct GitLabJobPayload {
-    #[serde(rename = "object_attributes")]
-    pub object_attributes: GitLabJobAttributes,
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub sha: String,
+    pub build_id: u64,
+    pub build_name: String,
+    pub build_stage: String,
+    pub build_status: String,
+    pub build_failure_reason: Option<String>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub build_created_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub build_started_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub build_finished_at: Option<DateTime<Utc>>,
+    pub pipeline_id: u64,
     pub project: GitLabProject,
-    pub commit: GitLabCommit,
+    pub user: Option<GitLabUser>,
+}
+
+/// GitLab's Pipeline Hook (`object_kind: "pipeline"`).
+#[derive(Deserialize)]
+pub struct GitLabPipelinePayload {
+    pub object_attributes: GitLabPipelineAttributes,
+    pub project: GitLabProject,
+    pub merge_request: Option<GitLabMergeRequest>,
+    pub user: Option<GitLabUser>,
+    #[serde(default)]
+    pub builds: Vec<GitLabBuild>,
 }
 
 #[derive(Deserialize)]
@@ -17,48 +44,332 @@ pub struct GitLabProject {
 }
 
 #[derive(Deserialize)]
-pub struct GitLabCommit {
-    pub id: String,
+pub struct GitLabUser {
+    pub username: String,
 }
 
 #[derive(Deserialize)]
-pub struct GitLabJobAttributes {
-    pub id: String,
-    pub pipeline_id: String,
+pub struct GitLabMergeRequest {
+    pub iid: u64,
+}
+
+#[derive(Deserialize)]
+pub struct GitLabPipelineAttributes {
+    pub id: u64,
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub sha: String,
+    pub source: Option<String>,
     pub status: String,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Seconds the pipeline waited for runners before starting.
+    pub queued_duration: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct GitLabBuild {
+    pub id: u64,
     pub name: String,
     pub stage: String,
+    pub status: String,
+    pub failure_reason: Option<String>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "timestamp")]
+    pub finished_at: Option<DateTime<Utc>>,
 }
 
-pub fn parse_payload(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
-    let payload: GitLabJobPayload = serde_json::from_slice(payload)
+#[derive(Deserialize)]
+struct GitLabObjectKind {
+    object_kind: Option<String>,
+    project: Option<GitLabProject>,
+}
+
+/// Routes a delivery to the parser for its `X-Gitlab-Event` header, falling
+/// back to the payload's `object_kind` when the header is missing.
+///
+/// A Pipeline Hook yields the pipeline event followed by one event per build.
+pub fn parse_event(event_name: Option<&str>, payload: &[u8]) -> Result<Vec<NormalizedEvent>, AppError> {
+    let kind: GitLabObjectKind = serde_json::from_slice(payload)
         .map_err(|e| AppError::BadRequest(format!("Invalid GitLab payload: {}", e)))?;
 
-    let event_type = match payload.object_attributes.status.as_str() {
-        "pending" | "running" => EventType::JobStarted,
-        "success" => EventType::JobSucceeded,
-        "failed" => EventType::JobFailed,
-        _ => EventType::JobFailed,
+    let object_kind = match event_name {
+        Some("Job Hook") => "build",
+        Some("Pipeline Hook") => "pipeline",
+        _ => kind.object_kind.as_deref().unwrap_or("unknown"),
+    };
+
+    let mut events = match object_kind {
+        "build" => vec![parse_job(payload)?],
+        "pipeline" => parse_pipeline(payload)?,
+        _ => {
+            let mut builder = EventBuilder::new(Platform::GitLab, EventType::Unknown);
+            if let Some(project) = &kind.project {
+                builder = with_project(builder, project);
+            }
+            vec![builder.build()?]
+        }
+    };
+
+    for event in &mut events {
+        event.raw_event_type = Some(object_kind.to_string());
+    }
+
+    Ok(events)
+}
+
+pub fn parse_job(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: GitLabJobPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid GitLab job payload: {}", e)))?;
+
+    let event_type = job_event_type(&payload.build_status, payload.build_failure_reason.as_deref());
+
+    with_project(EventBuilder::new(Platform::GitLab, event_type), &payload.project)
+        .pipeline_id(payload.pipeline_id.to_string())
+        .job_id(payload.build_id.to_string())
+        .logs_uri(job_logs_uri(&payload.project, payload.build_id))
+        .commit_sha(payload.sha.clone())
+        .branch(payload.git_ref.clone())
+        .job_name(payload.build_name.clone())
+        .stage(payload.build_stage.clone())
+        .actor(payload.user.as_ref().map(|user| user.username.clone()))
+        .created_at(payload.build_created_at)
+        .started_at(payload.build_started_at)
+        .completed_at(payload.build_finished_at)
+        .metadata("status", payload.build_status.clone())
+        .metadata("failure_reason", payload.build_failure_reason.clone())
+        .build()
+}
+
+pub fn parse_pipeline(payload: &[u8]) -> Result<Vec<NormalizedEvent>, AppError> {
+    let payload: GitLabPipelinePayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid GitLab pipeline payload: {}", e)))?;
+
+    let attributes = &payload.object_attributes;
+    let pipeline_id = attributes.id.to_string();
+
+    let event_type = match attributes.status.as_str() {
+        "success" => EventType::PipelineCompleted,
+        "failed" => EventType::PipelineErrored,
+        "canceled" => EventType::PipelineCancelled,
+        _ => EventType::Unknown,
+    };
+
+    // Pipeline hooks carry no start time, only how long the pipeline queued.
+    let started_at = match (attributes.created_at, attributes.queued_duration) {
+        (Some(created_at), Some(queued)) => {
+            Some(created_at + chrono::Duration::milliseconds((queued * 1000.0) as i64))
+        }
+        _ => None,
+    };
+
+    let pipeline_event = with_project(EventBuilder::new(Platform::GitLab, event_type), &payload.project)
+        .pipeline_id(pipeline_id.clone())
+        .trigger_source(attributes.source.clone())
+        .commit_sha(attributes.sha.clone())
+        .branch(attributes.git_ref.clone())
+        .pr_number(payload.merge_request.as_ref().map(|mr| mr.iid))
+        .actor(payload.user.as_ref().map(|user| user.username.clone()))
+        .created_at(attributes.created_at)
+        .started_at(started_at)
+        .completed_at(attributes.finished_at)
+        .metadata("status", attributes.status.clone())
+        .build()?;
+
+    let mut events = Vec::with_capacity(payload.builds.len() + 1);
+
+    for build in &payload.builds {
+        let event = EventBuilder::new(Platform::GitLab, job_event_type(&build.status, build.failure_reason.as_deref()))
+            .context_from(&pipeline_event)
+            .pipeline_id(pipeline_id.clone())
+            .job_id(build.id.to_string())
+            .logs_uri(job_logs_uri(&payload.project, build.id))
+            .job_name(build.name.clone())
+            .stage(build.stage.clone())
+            .created_at(build.created_at)
+            .started_at(build.started_at)
+            .completed_at(build.finished_at)
+            .metadata("status", build.status.clone())
+            .metadata("failure_reason", build.failure_reason.clone())
+            .build()?;
+        events.push(event);
+    }
+    events.insert(0, pipeline_event);
+
+    Ok(events)
+}
+
+fn with_project(builder: EventBuilder, project: &GitLabProject) -> EventBuilder {
+    builder
+        .repository(project.path_with_namespace.clone())
+        .instance_url(instance_url(project))
+}
+
+/// Base URL of the GitLab instance, derived from the project's web URL.
+fn instance_url(project: &GitLabProject) -> String {
+    let web_url = project.web_url.trim_end_matches('/');
+    web_url
+        .strip_suffix(&project.path_with_namespace)
+        .unwrap_or(web_url)
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn job_event_type(status: &str, failure_reason: Option<&str>) -> EventType {
+    match (status, failure_reason) {
+        ("created" | "pending" | "running" | "preparing" | "waiting_for_resource" | "scheduled", _) => {
+            EventType::JobStarted
+        }
+        ("success", _) => EventType::JobSucceeded,
+        ("failed", Some("job_execution_timeout")) => EventType::JobTimedOut,
+        ("failed", _) => EventType::JobFailed,
+        ("canceled" | "canceling", _) => EventType::JobCancelled,
+        ("skipped", _) => EventType::JobSkipped,
+        ("manual", _) => EventType::JobManual,
+        _ => EventType::Unknown,
+    }
+}
+
+fn job_logs_uri(project: &GitLabProject, job_id: u64) -> String {
+    format!("{}/-/jobs/{}", project.web_url, job_id)
+}
+
+/// Parses GitLab webhook timestamps, which are formatted as
+/// `2021-02-23 02:41:37 UTC` rather than RFC 3339 (newer releases send the
+/// latter for some hooks, so both are accepted).
+fn timestamp<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
     };
 
-    let logs_uri = format!(
-        "{}/-/jobs/{}",
-        payload.project.web_url,
-        payload.object_attributes.id
-    );
-
-    let mut event = NormalizedEvent::new(
-        Platform::GitLab,
-        payload.object_attributes.pipeline_id.clone(),
-        Some(payload.object_attributes.id.clone()),
-        event_type,
-        Some(logs_uri),
-    );
-
-    event.metadata.insert("project".into(), payload.project.path_with_namespace.clone());
-    event.metadata.insert("commit_sha".into(), payload.commit.id.clone());
-    event.metadata.insert("job_name".into(), payload.object_attributes.name.clone());
-    event.metadata.insert("stage".into(), payload.object_attributes.stage.clone());
-
-    Ok(event)
+    if let Ok(time) = DateTime::parse_from_rfc3339(&value) {
+        return Ok(Some(time.with_timezone(&Utc)));
+    }
+    if let Ok(time) = DateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S %z") {
+        return Ok(Some(time.with_timezone(&Utc)));
+    }
+    NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S UTC")
+        .map(|time| Some(time.and_utc()))
+        .map_err(|e| serde::de::Error::custom(format!("invalid timestamp {}: {}", value, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JOB_HOOK: &[u8] = include_bytes!("fixtures/job_hook.json");
+    const PIPELINE_HOOK: &[u8] = include_bytes!("fixtures/pipeline_hook.json");
+
+    fn at(time: &str) -> Option<DateTime<Utc>> {
+        Some(time.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_job_hooks() {
+        let events = parse_event(Some("Job Hook"), JOB_HOOK).unwrap();
+        let [event] = events.as_slice() else {
+            panic!("expected one event, got {:?}", events);
+        };
+
+        assert_eq!(event.event_type, EventType::JobFailed);
+        assert_eq!(event.pipeline_id, "123");
+        assert_eq!(event.job_id.as_deref(), Some("380"));
+        assert_eq!(event.job_name.as_deref(), Some("test"));
+        assert_eq!(event.stage.as_deref(), Some("test"));
+        assert_eq!(event.repository.as_deref(), Some("acme/widgets"));
+        assert_eq!(event.instance_url.as_deref(), Some("https://gitlab.example.com"));
+        assert_eq!(event.logs_uri.as_deref(), Some("https://gitlab.example.com/acme/widgets/-/jobs/380"));
+        assert_eq!(event.actor.as_deref(), Some("jdoe"));
+        assert_eq!(event.raw_event_type.as_deref(), Some("build"));
+        assert_eq!(event.started_at, at("2024-05-01T10:00:00Z"));
+        assert_eq!(event.completed_at, at("2024-05-01T10:04:00Z"));
+        assert_eq!(event.metadata.get("failure_reason").map(String::as_str), Some("script_failure"));
+    }
+
+    #[test]
+    fn parses_pipeline_hooks_with_their_builds() {
+        let events = parse_event(Some("Pipeline Hook"), PIPELINE_HOOK).unwrap();
+
+        let pipeline = &events[0];
+        assert_eq!(pipeline.event_type, EventType::PipelineErrored);
+        assert_eq!(pipeline.job_id, None);
+        assert_eq!(pipeline.pr_number, Some(7));
+        assert_eq!(pipeline.branch.as_deref(), Some("feature/widgets"));
+        assert_eq!(pipeline.trigger_source.as_deref(), Some("merge_request_event"));
+        assert_eq!(pipeline.created_at, at("2024-05-01T09:59:00Z"));
+        // Started once it had queued for 30 seconds.
+        assert_eq!(pipeline.started_at, at("2024-05-01T09:59:30Z"));
+        assert_eq!(pipeline.completed_at, at("2024-05-01T10:05:00Z"));
+
+        let builds: Vec<_> = events[1..]
+            .iter()
+            .map(|event| (event.job_id.as_deref().unwrap(), event.event_type.clone()))
+            .collect();
+        assert_eq!(
+            builds,
+            vec![
+                ("379", EventType::JobSucceeded),
+                ("380", EventType::JobFailed),
+                ("381", EventType::JobTimedOut),
+                ("382", EventType::JobManual),
+            ]
+        );
+        // Builds share the pipeline's context.
+        assert!(events[1..]
+            .iter()
+            .all(|event| event.pr_number == Some(7) && event.pipeline_id == "123"));
+    }
+
+    #[test]
+    fn dispatches_on_object_kind_without_a_header() {
+        assert_eq!(parse_event(None, JOB_HOOK).unwrap()[0].event_type, EventType::JobFailed);
+        assert_eq!(parse_event(None, PIPELINE_HOOK).unwrap().len(), 5);
+
+        let events = parse_event(Some("Note Hook"), br#"{"object_kind": "note"}"#).unwrap();
+        assert_eq!(events[0].event_type, EventType::Unknown);
+        assert_eq!(events[0].raw_event_type.as_deref(), Some("note"));
+    }
+
+    #[test]
+    fn maps_job_statuses() {
+        let cases = [
+            ("pending", None, EventType::JobStarted),
+            ("running", None, EventType::JobStarted),
+            ("success", None, EventType::JobSucceeded),
+            ("failed", Some("script_failure"), EventType::JobFailed),
+            ("failed", Some("job_execution_timeout"), EventType::JobTimedOut),
+            ("canceled", None, EventType::JobCancelled),
+            ("skipped", None, EventType::JobSkipped),
+            ("manual", None, EventType::JobManual),
+            ("unheard_of", None, EventType::Unknown),
+        ];
+        for (status, failure_reason, expected) in cases {
+            assert_eq!(job_event_type(status, failure_reason), expected, "{}", status);
+        }
+    }
+
+    #[test]
+    fn reads_both_timestamp_formats() {
+        #[derive(Deserialize)]
+        struct Stamped {
+            #[serde(default, deserialize_with = "timestamp")]
+            at: Option<DateTime<Utc>>,
+        }
+        let parse = |json: &str| serde_json::from_str::<Stamped>(json).map(|stamped| stamped.at);
+
+        assert_eq!(parse(r#"{"at": "2021-02-23 02:41:37 UTC"}"#).unwrap(), at("2021-02-23T02:41:37Z"));
+        assert_eq!(parse(r#"{"at": "2021-02-23 03:41:37 +0100"}"#).unwrap(), at("2021-02-23T02:41:37Z"));
+        assert_eq!(parse(r#"{"at": "2021-02-23T02:41:37Z"}"#).unwrap(), at("2021-02-23T02:41:37Z"));
+        assert_eq!(parse(r#"{"at": null}"#).unwrap(), None);
+        assert_eq!(parse("{}").unwrap(), None);
+        assert!(parse(r#"{"at": "yesterday"}"#).is_err());
+    }
 }
@@ -1,9 +1,6 @@
 use crate::errors::AppError;
 
+/// Compares the `X-Gitlab-Token` sent by GitLab in constant time.
 pub fn verify_token(token: &str, expected_token: &str) -> Result<(), AppError> {
-    if token != expected_token {
-        Err(AppError::Unauthorized)
-    } else {
-        Ok(())
-    }
+    crate::utils::auth::verify_token(token, expected_token)
 }
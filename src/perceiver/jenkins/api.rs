@@ -0,0 +1,99 @@
+use crate::errors::AppError;
+use crate::utils::http::is_under;
+use reqwest::{Client, StatusCode};
+
+/// Minimal Jenkins client used to re-run builds.
+///
+/// Authenticates with `JENKINS_USER` and `JENKINS_API_TOKEN`; requests made
+/// with an API token need no CSRF crumb. Only URLs on the server at
+/// `JENKINS_URL` are requested, since build URLs come from webhook payloads.
+pub struct JenkinsClient {
+    http: Client,
+    base_url: Option<String>,
+    credentials: Option<(String, String)>,
+}
+
+impl JenkinsClient {
+    pub fn new(base_url: Option<String>, credentials: Option<(String, String)>) -> Self {
+        Self {
+            http: Client::new(),
+            base_url: base_url.map(|url| url.trim_end_matches('/').to_string()),
+            credentials,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let credentials = match (std::env::var("JENKINS_USER"), std::env::var("JENKINS_API_TOKEN")) {
+            (Ok(user), Ok(token)) => Some((user, token)),
+            _ => None,
+        };
+        Self::new(std::env::var("JENKINS_URL").ok(), credentials)
+    }
+
+    /// Re-runs the build at `build_url` with the parameters it was started
+    /// with, through the Rebuilder plugin. Without the plugin the job is
+    /// built again with its default parameters.
+    pub async fn rebuild(&self, build_url: &str) -> Result<(), AppError> {
+        let build_url = build_url.trim_end_matches('/');
+        if !self.serves(build_url) {
+            return Err(AppError::BadRequest(format!(
+                "Jenkins build URL {} is not on the server at JENKINS_URL",
+                build_url
+            )));
+        }
+
+        let response = self.post(&format!("{}/rebuild/", build_url)).send().await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+            return Ok(());
+        }
+
+        let job_url = build_url
+            .rsplit_once('/')
+            .map(|(job_url, _number)| job_url)
+            .ok_or_else(|| AppError::BadRequest(format!("Invalid Jenkins build URL {}", build_url)))?;
+        self.post(&format!("{}/build", job_url))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Whether `url` is on the configured server.
+    fn serves(&self, url: &str) -> bool {
+        self.base_url.as_deref().is_some_and(|base_url| is_under(url, base_url))
+    }
+
+    fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        let req = self.http.post(url);
+        match &self.credentials {
+            Some((user, token)) => req.basic_auth(user, Some(token)),
+            None => req,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_builds_on_other_servers() {
+        let credentials = Some(("ci".to_string(), "secret".to_string()));
+        let client = JenkinsClient::new(Some("https://ci.example.com/".to_string()), credentials.clone());
+
+        for build_url in [
+            "https://attacker.example.net/job/app/7/",
+            "https://ci.example.com.attacker.net/job/app/7/",
+        ] {
+            assert!(matches!(client.rebuild(build_url).await, Err(AppError::BadRequest(_))));
+        }
+
+        let unconfigured = JenkinsClient::new(None, credentials);
+        assert!(matches!(
+            unconfigured.rebuild("https://ci.example.com/job/app/7/").await,
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
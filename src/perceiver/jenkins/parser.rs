@@ -0,0 +1,111 @@
+use serde::Deserialize;
+use crate::errors::AppError;
+use crate::perceiver::builder::EventBuilder;
+use crate::perceiver::event::{EventType, NormalizedEvent, Platform};
+
+/// Build notification as sent by the Jenkins Notification Plugin (and
+/// generic-webhook setups that mimic its JSON format).
+#[derive(Deserialize)]
+pub struct JenkinsNotificationPayload {
+    pub name: String,
+    pub build: JenkinsBuild,
+}
+
+#[derive(Deserialize)]
+pub struct JenkinsBuild {
+    pub full_url: String,
+    pub number: u64,
+    pub phase: String,
+    pub status: Option<String>,
+    pub scm: Option<JenkinsScm>,
+}
+
+#[derive(Deserialize)]
+pub struct JenkinsScm {
+    pub url: Option<String>,
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+}
+
+pub fn parse_payload(payload: &[u8]) -> Result<NormalizedEvent, AppError> {
+    let payload: JenkinsNotificationPayload = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Jenkins payload: {}", e)))?;
+
+    let build = &payload.build;
+
+    // Jenkins reports COMPLETED and then FINALIZED for every build; only the
+    // latter guarantees the console log is complete, so it is the one we act on.
+    let event_type = match build.phase.as_str() {
+        "QUEUED" | "STARTED" => EventType::JobStarted,
+        "FINALIZED" => match build.status.as_deref() {
+            Some("SUCCESS") => EventType::JobSucceeded,
+            Some("ABORTED") => EventType::JobCancelled,
+            Some("NOT_BUILT") => EventType::JobSkipped,
+            _ => EventType::JobFailed,
+        },
+        _ => EventType::Unknown,
+    };
+
+    let build_url = build.full_url.trim_end_matches('/');
+    let logs_uri = format!("{}/consoleText", build_url);
+
+    let scm = build.scm.as_ref();
+    let branch = scm
+        .and_then(|scm| scm.branch.as_deref())
+        .map(|branch| branch.strip_prefix("origin/").unwrap_or(branch).to_string());
+
+    EventBuilder::new(Platform::Jenkins, event_type)
+        .pipeline_id(format!("{}/{}", payload.name, build.number))
+        .job_id(build.number.to_string())
+        .logs_uri(logs_uri)
+        .raw_event_type(build.phase.to_lowercase())
+        .repository(scm.and_then(|scm| scm.url.as_deref()).and_then(repository_path))
+        .commit_sha(scm.and_then(|scm| scm.commit.clone()))
+        .branch(branch)
+        .job_name(payload.name.clone())
+        .metadata("build_number", build.number.to_string())
+        .metadata("build_url", build_url.to_string())
+        .metadata("scm_url", scm.and_then(|scm| scm.url.clone()))
+        .metadata("status", build.status.clone())
+        .build()
+}
+
+/// Repository path, e.g. `owner/name` or a GitLab project path, from an SCM
+/// URL such as `https://github.com/acme/widgets.git` or
+/// `git@github.com:acme/widgets.git`.
+fn repository_path(url: &str) -> Option<String> {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        // scp-like syntax: `[user@]host:path`.
+        None => url.split_once(':')?.1,
+    };
+
+    let path = path.trim_matches('/');
+    path.contains('/').then(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_path_strips_host_and_suffix() {
+        for url in [
+            "https://github.com/acme/widgets.git",
+            "https://github.com/acme/widgets/",
+            "git@github.com:acme/widgets.git",
+            "ssh://git@git.corp.example:7999/acme/widgets.git",
+        ] {
+            assert_eq!(repository_path(url).as_deref(), Some("acme/widgets"), "{}", url);
+        }
+
+        assert_eq!(
+            repository_path("https://gitlab.com/group/subgroup/project.git").as_deref(),
+            Some("group/subgroup/project")
+        );
+        assert_eq!(repository_path("https://git.example/widgets.git"), None);
+        assert_eq!(repository_path("/srv/git/widgets"), None);
+    }
+}
@@ -0,0 +1,6 @@
+use crate::errors::AppError;
+
+/// Compares the shared token sent by Jenkins in constant time.
+pub fn verify_token(token: &str, expected_token: &str) -> Result<(), AppError> {
+    crate::utils::auth::verify_token(token, expected_token)
+}
@@ -0,0 +1,63 @@
+use crate::errors::SaturatedLimit;
+use crate::perceiver::filter::EventFilter;
+use crate::perceiver::queue::EventQueue;
+use crate::perceiver::webhook::WebhookSource;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Counters for webhook deliveries, served in the Prometheus text format on
+/// `/metrics`.
+#[derive(Default)]
+pub struct DeliveryMetrics {
+    /// Deliveries turned away, by source and the limit that was reached.
+    rejected: Mutex<BTreeMap<(&'static str, SaturatedLimit), u64>>,
+}
+
+impl DeliveryMetrics {
+    pub fn record_rejection(&self, source: WebhookSource, limit: SaturatedLimit) {
+        *self
+            .rejected
+            .lock()
+            .unwrap()
+            .entry((source.as_str(), limit))
+            .or_default() += 1;
+    }
+
+    pub fn render(&self, queue: &EventQueue, filter: &EventFilter) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP webhook_deliveries_rejected_total Deliveries rejected because the event queue was saturated.");
+        let _ = writeln!(out, "# TYPE webhook_deliveries_rejected_total counter");
+        for ((source, limit), count) in self.rejected.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "webhook_deliveries_rejected_total{{source=\"{}\",limit=\"{}\"}} {}",
+                source, limit, count
+            );
+        }
+
+        let _ = writeln!(out, "# HELP events_filtered_total Events dropped by the ingestion filter, by the rule that dropped them.");
+        let _ = writeln!(out, "# TYPE events_filtered_total counter");
+        for ((platform, rule), count) in filter.filtered_counts() {
+            let _ = writeln!(
+                out,
+                "events_filtered_total{{platform=\"{}\",rule=\"{}\"}} {}",
+                label(&platform),
+                label(&rule),
+                count
+            );
+        }
+
+        let _ = writeln!(out, "# HELP event_queue_depth Events queued and not yet acknowledged by the agent.");
+        let _ = writeln!(out, "# TYPE event_queue_depth gauge");
+        let _ = writeln!(out, "event_queue_depth {}", queue.len());
+
+        out
+    }
+}
+
+/// Escapes a label value for the Prometheus text format.
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
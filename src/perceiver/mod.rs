@@ -1,6 +1,16 @@
+pub mod archive;
+pub mod bitbucket;
 pub mod builder;
 pub mod client;
+pub mod dedup;
 pub mod event;
+pub mod filter;
+pub mod gitea;
 pub mod github;
 pub mod gitlab;
+pub mod jenkins;
+pub mod metrics;
+pub mod queue;
+pub mod review;
+pub mod secrets;
 pub mod webhook;
\ No newline at end of file
@@ -0,0 +1,621 @@
+use crate::errors::{AppError, SaturatedLimit};
+use crate::perceiver::event::NormalizedEvent;
+use crate::utils::env::env_parse;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Acknowledgements appended before the log is rewritten without them.
+const DEFAULT_COMPACT_AFTER: usize = 1_000;
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_WAIT_BUDGET_MS: u64 = 2_000;
+const DEFAULT_RETRY_AFTER_SECS: u64 = 30;
+
+/// Queue of events waiting for the agent.
+///
+/// With a path configured, every event is appended to a write-ahead log and
+/// synced to disk before [`EventQueue::push`] returns, so a webhook is only
+/// acknowledged once its events survive a crash. The agent takes events with
+/// [`EventQueue::next`] and acknowledges them with [`EventQueue::ack`] once
+/// handled; events still unacknowledged when the process stops are
+/// delivered again on the next start.
+///
+/// The log holds one JSON record per line, either
+/// `{"op":"enqueue","seq":1,"event":{...}}` or `{"op":"ack","seq":1}`.
+/// Writes run on the blocking thread pool, outside the queue's lock.
+pub struct EventQueue {
+    state: Arc<Mutex<QueueState>>,
+    available: Notify,
+    freed: Notify,
+    limits: QueueLimits,
+    log: Option<Arc<QueueLog>>,
+}
+
+/// Admission limits applied to webhook deliveries by [`EventQueue::offer`].
+///
+/// Counts are of unacknowledged events. A delivery is always admitted when
+/// nothing is queued for its source or repository, so a single large
+/// delivery cannot be locked out by a limit smaller than itself.
+#[derive(Debug, Clone)]
+pub struct QueueLimits {
+    pub capacity: usize,
+    pub per_source: Option<usize>,
+    pub per_repository: Option<usize>,
+    /// How long a delivery may wait for room before it is turned away.
+    pub wait_budget: Duration,
+    /// Delay suggested to turned-away senders through `Retry-After`.
+    pub retry_after: Duration,
+}
+
+/// An event taken from the queue, to be acknowledged by `seq`.
+pub struct QueuedEvent {
+    pub seq: u64,
+    pub event: NormalizedEvent,
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_seq: u64,
+    pending: VecDeque<u64>,
+    /// Every unacknowledged event, pending or taken.
+    events: BTreeMap<u64, NormalizedEvent>,
+    /// Events counted against the limits whose records are still being
+    /// written; compaction leaves them for their own append.
+    unwritten: BTreeSet<u64>,
+}
+
+struct QueueLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    compact_after: usize,
+    acked: Mutex<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Record {
+    Enqueue { seq: u64, event: Box<NormalizedEvent> },
+    Ack { seq: u64 },
+}
+
+impl EventQueue {
+    /// Queue held only in memory; queued events are lost on restart.
+    pub fn in_memory() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(QueueState::default())),
+            available: Notify::new(),
+            freed: Notify::new(),
+            limits: QueueLimits::default(),
+            log: None,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: QueueLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Opens the log at `path`, restoring every event it holds that was
+    /// never acknowledged.
+    pub fn open(path: PathBuf, compact_after: usize) -> Result<Self, AppError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut state = QueueState::default();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // A crash mid-append leaves a truncated last record.
+                match serde_json::from_str(&line) {
+                    Ok(Record::Enqueue { seq, event }) => {
+                        state.events.insert(seq, *event);
+                        state.next_seq = state.next_seq.max(seq + 1);
+                    }
+                    Ok(Record::Ack { seq }) => {
+                        state.events.remove(&seq);
+                    }
+                    Err(e) => warn!("Skipping unreadable record {} in {:?}: {}", index + 1, path, e),
+                }
+            }
+        }
+        state.pending = state.events.keys().copied().collect();
+
+        if !state.pending.is_empty() {
+            info!("Restored {} unacknowledged events from {:?}", state.pending.len(), path);
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let state = Arc::new(Mutex::new(state));
+        let log = Arc::new(QueueLog {
+            path,
+            file: Mutex::new(file),
+            compact_after,
+            acked: Mutex::new(0),
+        });
+        // Drop the acknowledged records left over from the previous run.
+        log.compact(&state)?;
+
+        Ok(Self {
+            state,
+            available: Notify::new(),
+            freed: Notify::new(),
+            limits: QueueLimits::default(),
+            log: Some(log),
+        })
+    }
+
+    /// Opens the log at `EVENT_QUEUE_PATH`, or an in-memory queue when unset,
+    /// with limits from [`QueueLimits::from_env`].
+    pub fn from_env() -> Result<Self, AppError> {
+        let compact_after = env_parse("EVENT_QUEUE_COMPACT_AFTER", DEFAULT_COMPACT_AFTER)?;
+
+        let queue = match std::env::var("EVENT_QUEUE_PATH") {
+            Ok(path) => Self::open(PathBuf::from(path), compact_after)?,
+            Err(_) => Self::in_memory(),
+        };
+        Ok(queue.with_limits(QueueLimits::from_env()?))
+    }
+
+    /// Appends `event`, returning once it is durably recorded.
+    pub async fn push(&self, event: NormalizedEvent) -> Result<(), AppError> {
+        self.push_all(vec![event]).await
+    }
+
+    /// Appends all of a delivery's `events` with a single write and sync,
+    /// regardless of the queue's limits.
+    pub async fn push_all(&self, events: Vec<NormalizedEvent>) -> Result<(), AppError> {
+        let records = self.reserve(&mut self.state.lock().unwrap(), events);
+        self.append(records).await
+    }
+
+    /// Appends a delivery's `events` once the queue's limits leave room for
+    /// them, waiting up to the limits' wait budget for the agent to catch up.
+    /// Returns [`AppError::Saturated`] if there is still no room by then.
+    pub async fn offer(&self, mut events: Vec<NormalizedEvent>) -> Result<(), AppError> {
+        let deadline = Instant::now() + self.limits.wait_budget;
+
+        loop {
+            // Registered before checking so an ack in between is not missed.
+            let freed = self.freed.notified();
+
+            let admitted = {
+                let mut state = self.state.lock().unwrap();
+                match self.limits.exceeded(&state, &events) {
+                    None => Ok(self.reserve(&mut state, std::mem::take(&mut events))),
+                    Some(limit) => Err(limit),
+                }
+            };
+            match admitted {
+                Ok(records) => return self.append(records).await,
+                Err(limit) if Instant::now() >= deadline => {
+                    return Err(AppError::Saturated {
+                        limit,
+                        retry_after: self.limits.retry_after,
+                    })
+                }
+                Err(_) => {}
+            }
+
+            // On timeout the next pass reports the limit still exceeded.
+            let _ = tokio::time::timeout_at(deadline, freed).await;
+        }
+    }
+
+    /// Takes sequence numbers for `events` and counts them against the
+    /// limits, returning the records to log for them.
+    ///
+    /// Numbers are never handed out twice, even when the append fails after
+    /// writing part of its records.
+    fn reserve(&self, state: &mut QueueState, events: Vec<NormalizedEvent>) -> Vec<Record> {
+        let mut records = Vec::with_capacity(events.len());
+        for event in events {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            if self.log.is_some() {
+                state.unwritten.insert(seq);
+            }
+            records.push(Record::Enqueue { seq, event: Box::new(event.clone()) });
+            state.events.insert(seq, event);
+        }
+        records
+    }
+
+    /// Logs the records of reserved events, then makes them available to
+    /// [`EventQueue::next`]. If the log cannot be written the reservation
+    /// is released.
+    async fn append(&self, records: Vec<Record>) -> Result<(), AppError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let seqs: Vec<u64> = records.iter().map(Record::seq).collect();
+
+        if let Some(log) = &self.log {
+            if let Err(e) = log.clone().append(records, self.state.clone()).await {
+                let mut state = self.state.lock().unwrap();
+                for seq in &seqs {
+                    state.events.remove(seq);
+                    state.unwritten.remove(seq);
+                }
+                drop(state);
+                self.freed.notify_waiters();
+                return Err(e);
+            }
+        }
+
+        self.state.lock().unwrap().pending.extend(seqs);
+        self.available.notify_one();
+        Ok(())
+    }
+
+    /// Waits for the next event. It stays in the queue until acknowledged.
+    pub async fn next(&self) -> QueuedEvent {
+        loop {
+            // Registered before checking so a push in between is not missed.
+            let available = self.available.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                while let Some(seq) = state.pending.pop_front() {
+                    if let Some(event) = state.events.get(&seq) {
+                        return QueuedEvent {
+                            seq,
+                            event: event.clone(),
+                        };
+                    }
+                }
+            }
+
+            available.await;
+        }
+    }
+
+    /// Marks an event as handled so it is not delivered again.
+    pub async fn ack(&self, seq: u64) -> Result<(), AppError> {
+        let removed = self.state.lock().unwrap().events.remove(&seq).is_some();
+        if !removed {
+            return Ok(());
+        }
+        self.freed.notify_waiters();
+
+        let Some(log) = &self.log else {
+            return Ok(());
+        };
+        log.clone().append(vec![Record::Ack { seq }], self.state.clone()).await?;
+
+        let needs_compaction = {
+            let mut acked = log.acked.lock().unwrap();
+            *acked += 1;
+            *acked >= log.compact_after
+        };
+        if needs_compaction {
+            let (log, state) = (log.clone(), self.state.clone());
+            let result = tokio::task::spawn_blocking(move || log.compact(&state))
+                .await
+                .map_err(|e| AppError::Internal(e.into()))
+                .and_then(|result| result);
+            if let Err(e) = result {
+                warn!("Failed to compact event queue: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of events not yet acknowledged.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+}
+
+impl Record {
+    fn seq(&self) -> u64 {
+        match self {
+            Record::Enqueue { seq, .. } | Record::Ack { seq } => *seq,
+        }
+    }
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            per_source: None,
+            per_repository: None,
+            wait_budget: Duration::from_millis(DEFAULT_WAIT_BUDGET_MS),
+            retry_after: Duration::from_secs(DEFAULT_RETRY_AFTER_SECS),
+        }
+    }
+}
+
+impl QueueLimits {
+    /// Reads `EVENT_QUEUE_CAPACITY`, `EVENT_QUEUE_SOURCE_LIMIT`,
+    /// `EVENT_QUEUE_REPOSITORY_LIMIT`, `EVENT_QUEUE_WAIT_MS` and
+    /// `EVENT_QUEUE_RETRY_AFTER_SECS`.
+    pub fn from_env() -> Result<Self, AppError> {
+        let optional = |name: &str| -> Result<Option<usize>, AppError> {
+            std::env::var(name).ok().map(|_| env_parse(name, 0)).transpose()
+        };
+
+        Ok(Self {
+            capacity: env_parse("EVENT_QUEUE_CAPACITY", DEFAULT_CAPACITY)?,
+            per_source: optional("EVENT_QUEUE_SOURCE_LIMIT")?,
+            per_repository: optional("EVENT_QUEUE_REPOSITORY_LIMIT")?,
+            wait_budget: Duration::from_millis(env_parse("EVENT_QUEUE_WAIT_MS", DEFAULT_WAIT_BUDGET_MS)?),
+            retry_after: Duration::from_secs(env_parse("EVENT_QUEUE_RETRY_AFTER_SECS", DEFAULT_RETRY_AFTER_SECS)?),
+        })
+    }
+
+    /// The limit `events` would push past, if any.
+    fn exceeded(&self, state: &QueueState, events: &[NormalizedEvent]) -> Option<SaturatedLimit> {
+        let queued = state.events.values();
+
+        if !state.events.is_empty() && state.events.len() + events.len() > self.capacity {
+            return Some(SaturatedLimit::Queue);
+        }
+
+        let first = events.first()?;
+
+        if let Some(limit) = self.per_source {
+            let count = queued.clone().filter(|e| e.platform == first.platform).count();
+            if count > 0 && count + events.len() > limit {
+                return Some(SaturatedLimit::Source);
+            }
+        }
+
+        if let (Some(limit), Some(repository)) = (self.per_repository, &first.repository) {
+            let count = queued
+                .filter(|e| e.platform == first.platform && e.repository.as_ref() == Some(repository))
+                .count();
+            if count > 0 && count + events.len() > limit {
+                return Some(SaturatedLimit::Repository);
+            }
+        }
+
+        None
+    }
+}
+
+impl QueueLog {
+    /// Writes and syncs `records` on the blocking thread pool. A failed write
+    /// is cut back off the log so no partial record is left behind.
+    async fn append(self: Arc<Self>, records: Vec<Record>, state: Arc<Mutex<QueueState>>) -> Result<(), AppError> {
+        let mut lines = Vec::new();
+        for record in &records {
+            serde_json::to_writer(&mut lines, record).map_err(|e| AppError::Internal(e.into()))?;
+            lines.push(b'\n');
+        }
+
+        tokio::task::spawn_blocking(move || {
+            let mut file = self.file.lock().unwrap();
+            let len = file.metadata()?.len();
+            let written = file.write_all(&lines).and_then(|()| file.sync_data());
+            if let Err(e) = written {
+                let _ = file.set_len(len);
+                return Err(e.into());
+            }
+
+            // Still holding the file, so compaction sees these events as
+            // written exactly when their records are in the file it replaces.
+            let mut state = state.lock().unwrap();
+            for record in &records {
+                if let Record::Enqueue { seq, .. } = record {
+                    state.unwritten.remove(seq);
+                }
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.into()))?
+    }
+
+    /// Rewrites the log with only the unacknowledged events.
+    fn compact(&self, state: &Mutex<QueueState>) -> Result<(), AppError> {
+        // Held throughout so no record is appended to the file being replaced.
+        let mut file = self.file.lock().unwrap();
+
+        let mut contents = Vec::new();
+        {
+            let state = state.lock().unwrap();
+            for (seq, event) in &state.events {
+                if state.unwritten.contains(seq) {
+                    continue;
+                }
+                serde_json::to_writer(&mut contents, &Record::Enqueue { seq: *seq, event: Box::new(event.clone()) })
+                    .map_err(|e| AppError::Internal(e.into()))?;
+                contents.push(b'\n');
+            }
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&contents)?;
+        tmp.sync_all()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        *file = OpenOptions::new().append(true).open(&self.path)?;
+        *self.acked.lock().unwrap() = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perceiver::event::{EventType, Platform};
+
+    fn event(pipeline_id: &str) -> NormalizedEvent {
+        NormalizedEvent::new(Platform::GitHub, pipeline_id.to_string(), None, EventType::JobFailed, None)
+    }
+
+    fn log_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("event-queue-{}-{}.log", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn records(path: &PathBuf) -> Vec<Record> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn restores_unacknowledged_events_on_open() {
+        let path = log_path("restore");
+
+        let queue = EventQueue::open(path.clone(), 100).unwrap();
+        queue.push_all(vec![event("1"), event("2"), event("3")]).await.unwrap();
+        let first = queue.next().await;
+        queue.ack(first.seq).await.unwrap();
+        drop(queue);
+
+        let queue = EventQueue::open(path.clone(), 100).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.next().await.event.pipeline_id, "2");
+        assert_eq!(queue.next().await.event.pipeline_id, "3");
+
+        // Sequence numbers carry on from the restored events.
+        queue.push(event("4")).await.unwrap();
+        assert_eq!(queue.next().await.seq, 3);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn acknowledged_events_are_not_delivered_again() {
+        let path = log_path("ack");
+
+        let queue = EventQueue::open(path.clone(), 100).unwrap();
+        queue.push(event("1")).await.unwrap();
+        let queued = queue.next().await;
+        queue.ack(queued.seq).await.unwrap();
+        // A second acknowledgement is a no-op.
+        queue.ack(queued.seq).await.unwrap();
+        assert!(queue.is_empty());
+
+        let records = records(&path);
+        assert_eq!(records.len(), 2);
+        assert!(matches!(records[1], Record::Ack { seq: 0 }));
+        drop(queue);
+
+        assert!(EventQueue::open(path.clone(), 100).unwrap().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_only_unacknowledged_events() {
+        let path = log_path("compact");
+
+        let queue = EventQueue::open(path.clone(), 2).unwrap();
+        queue.push_all(vec![event("1"), event("2"), event("3")]).await.unwrap();
+        for _ in 0..2 {
+            let queued = queue.next().await;
+            queue.ack(queued.seq).await.unwrap();
+        }
+
+        let records = records(&path);
+        assert_eq!(records.len(), 1);
+        assert!(matches!(&records[0], Record::Enqueue { seq: 2, event } if event.pipeline_id == "3"));
+        drop(queue);
+
+        let queue = EventQueue::open(path.clone(), 2).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.next().await.seq, 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn limited(capacity: usize, per_source: Option<usize>, per_repository: Option<usize>) -> EventQueue {
+        EventQueue::in_memory().with_limits(QueueLimits {
+            capacity,
+            per_source,
+            per_repository,
+            wait_budget: Duration::ZERO,
+            retry_after: Duration::from_secs(30),
+        })
+    }
+
+    fn from(platform: Platform, repository: &str) -> NormalizedEvent {
+        let mut event = NormalizedEvent::new(platform, "1".to_string(), None, EventType::JobFailed, None);
+        event.repository = Some(repository.to_string());
+        event
+    }
+
+    fn saturated(result: Result<(), AppError>) -> SaturatedLimit {
+        match result {
+            Err(AppError::Saturated { limit, retry_after }) => {
+                assert_eq!(retry_after, Duration::from_secs(30));
+                limit
+            }
+            other => panic!("expected the delivery to be turned away, got {:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn turns_away_deliveries_past_capacity() {
+        let queue = limited(2, None, None);
+
+        // An empty queue admits a delivery larger than its capacity.
+        queue.offer(vec![event("1"), event("2"), event("3")]).await.unwrap();
+        assert_eq!(saturated(queue.offer(vec![event("4")]).await), SaturatedLimit::Queue);
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn limits_each_source_and_repository() {
+        let queue = limited(100, Some(2), None);
+        queue.offer(vec![from(Platform::GitHub, "acme/widgets")]).await.unwrap();
+        queue.offer(vec![from(Platform::GitHub, "acme/gadgets")]).await.unwrap();
+        assert_eq!(
+            saturated(queue.offer(vec![from(Platform::GitHub, "acme/tools")]).await),
+            SaturatedLimit::Source
+        );
+        queue.offer(vec![from(Platform::GitLab, "acme/tools")]).await.unwrap();
+
+        let queue = limited(100, None, Some(1));
+        queue.offer(vec![from(Platform::GitHub, "acme/widgets")]).await.unwrap();
+        assert_eq!(
+            saturated(queue.offer(vec![from(Platform::GitHub, "acme/widgets")]).await),
+            SaturatedLimit::Repository
+        );
+        queue.offer(vec![from(Platform::GitHub, "acme/gadgets")]).await.unwrap();
+        queue.offer(vec![from(Platform::GitLab, "acme/widgets")]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn waits_for_room_within_the_budget() {
+        let queue = Arc::new(EventQueue::in_memory().with_limits(QueueLimits {
+            capacity: 1,
+            wait_budget: Duration::from_secs(5),
+            ..QueueLimits::default()
+        }));
+        queue.offer(vec![event("1")]).await.unwrap();
+
+        let offered = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.offer(vec![event("2")]).await }
+        });
+        let queued = queue.next().await;
+        queue.ack(queued.seq).await.unwrap();
+
+        offered.await.unwrap().unwrap();
+        assert_eq!(queue.next().await.event.pipeline_id, "2");
+    }
+}
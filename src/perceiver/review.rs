@@ -0,0 +1,153 @@
+use crate::errors::AppError;
+use crate::perceiver::bitbucket::api::BitbucketClient;
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use crate::perceiver::gitea::api::GiteaClient;
+use crate::perceiver::github::api::GitHubClient;
+use crate::perceiver::gitlab::api::GitLabClient;
+use crate::utils::env::env_parse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_TTL_SECS: u64 = 60 * 60;
+/// Commits without a review are re-checked sooner, since a pull request is
+/// often opened shortly after the first push.
+const DEFAULT_MISS_TTL_SECS: u64 = 60;
+
+/// Fills in the pull request (or GitLab merge request) an event belongs to
+/// when its payload did not name one, by looking its commit up through the
+/// platform API.
+///
+/// Lookups are cached per instance, repository and commit, since every job
+/// of a pipeline shares the same commit.
+pub struct ReviewResolver {
+    capacity: usize,
+    ttl: Duration,
+    miss_ttl: Duration,
+    cache: Mutex<HashMap<String, CachedReview>>,
+}
+
+struct CachedReview {
+    pr_number: Option<u64>,
+    expires_at: Instant,
+}
+
+impl ReviewResolver {
+    pub fn new(capacity: usize, ttl: Duration, miss_ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            miss_ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, AppError> {
+        Ok(Self::new(
+            env_parse("REVIEW_CACHE_CAPACITY", DEFAULT_CAPACITY)?,
+            Duration::from_secs(env_parse("REVIEW_CACHE_TTL_SECS", DEFAULT_TTL_SECS)?),
+            Duration::from_secs(env_parse("REVIEW_CACHE_MISS_TTL_SECS", DEFAULT_MISS_TTL_SECS)?),
+        ))
+    }
+
+    /// Sets `event.pr_number` from the commit's review, if it has one. Lookup
+    /// failures are logged and leave the event unchanged.
+    pub async fn resolve(&self, event: &mut NormalizedEvent) {
+        if event.pr_number.is_some() {
+            return;
+        }
+        let (Some(repository), Some(commit)) = (&event.repository, &event.commit_sha) else {
+            return;
+        };
+
+        let key = format!(
+            "{:?}:{}:{}@{}",
+            event.platform,
+            event.instance_url.as_deref().unwrap_or_default(),
+            repository,
+            commit
+        );
+        if let Some(pr_number) = self.cached(&key) {
+            event.pr_number = pr_number;
+            return;
+        }
+
+        match lookup(event, repository, commit).await {
+            Ok(pr_number) => {
+                debug!("Resolved {}@{} to review {:?}", repository, commit, pr_number);
+                self.store(key, pr_number);
+                event.pr_number = pr_number;
+            }
+            Err(e) => warn!(
+                "Failed to look up the review for {}@{}: {}",
+                repository, commit, e
+            ),
+        }
+    }
+
+    fn cached(&self, key: &str) -> Option<Option<u64>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(key)
+            .filter(|cached| cached.expires_at > Instant::now())
+            .map(|cached| cached.pr_number)
+    }
+
+    fn store(&self, key: String, pr_number: Option<u64>) {
+        let now = Instant::now();
+        let ttl = if pr_number.is_some() { self.ttl } else { self.miss_ttl };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.capacity {
+            cache.retain(|_, cached| cached.expires_at > now);
+        }
+        if cache.len() >= self.capacity {
+            // Still full of live entries: drop the one closest to expiry.
+            if let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.expires_at)
+                .map(|(key, _)| key.clone())
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            key,
+            CachedReview {
+                pr_number,
+                expires_at: now + ttl,
+            },
+        );
+    }
+}
+
+async fn lookup(event: &NormalizedEvent, repository: &str, commit: &str) -> Result<Option<u64>, AppError> {
+    match event.platform {
+        Platform::GitHub => {
+            GitHubClient::for_event(event)
+                .await?
+                .pull_request_for_commit(repository, commit)
+                .await
+        }
+        Platform::GitLab => {
+            GitLabClient::for_event(event)
+                .await?
+                .merge_request_for_commit(repository, commit)
+                .await
+        }
+        Platform::Gitea => {
+            GiteaClient::from_env()?
+                .pull_request_for_commit(repository, commit)
+                .await
+        }
+        Platform::Bitbucket => {
+            BitbucketClient::from_env()
+                .pull_request_for_commit(repository, commit)
+                .await
+        }
+        // Jenkins builds are not tied to a hosted repository.
+        Platform::Jenkins => Ok(None),
+    }
+}
@@ -0,0 +1,286 @@
+use crate::errors::AppError;
+use crate::perceiver::webhook::WebhookSource;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Separator in secret directory file names between the source and the
+/// repository, and standing in for `/` within the repository, since mounted
+/// secret keys cannot contain path separators.
+const PATH_SEPARATOR: char = '.';
+
+/// Escapes a literal `.` or `_` in a repository within a file name.
+const ESCAPE: char = '_';
+
+/// Resolves the webhook secrets currently valid for a repository.
+///
+/// Each repository (GitHub/Gitea/Bitbucket `owner/name`, GitLab project path)
+/// may have several secrets at once so old and new are both accepted while a
+/// secret is being rotated. Repositories without an entry fall back to the
+/// source's default secrets, and finally to the legacy per-source environment
+/// variable (e.g. `GITHUB_WEBHOOK_SECRET`).
+///
+/// Secrets come from either a YAML file:
+///
+/// ```yaml
+/// github:
+///   default: [org-wide-secret]
+///   repositories:
+///     acme/widgets: [old-secret, new-secret]
+/// ```
+///
+/// or a directory with one file per entry, named `<source>` for the default
+/// or `<source>.<owner>.<name>` for a repository, holding one secret per
+/// line. A `.` or `_` within the repository is written `_.` or `__`, so
+/// `acme/widgets.js` is `github.acme.widgets_.js`; names only use characters
+/// valid in Kubernetes Secret keys. Files whose names cannot be decoded are
+/// skipped.
+pub struct SecretResolver {
+    location: Option<SecretLocation>,
+    table: RwLock<SecretTable>,
+}
+
+#[derive(Debug, Clone)]
+enum SecretLocation {
+    File(PathBuf),
+    Directory(PathBuf),
+}
+
+type SecretTable = HashMap<String, SourceSecrets>;
+
+#[derive(Debug, Default, Deserialize)]
+struct SourceSecrets {
+    #[serde(default)]
+    default: Vec<String>,
+    #[serde(default)]
+    repositories: HashMap<String, Vec<String>>,
+}
+
+impl SecretResolver {
+    /// Resolver using only the legacy per-source environment variables.
+    pub fn from_env_vars() -> Self {
+        Self {
+            location: None,
+            table: RwLock::new(SecretTable::new()),
+        }
+    }
+
+    pub fn from_file(path: PathBuf) -> Result<Self, AppError> {
+        Self::load(SecretLocation::File(path))
+    }
+
+    pub fn from_directory(path: PathBuf) -> Result<Self, AppError> {
+        Self::load(SecretLocation::Directory(path))
+    }
+
+    /// Builds a resolver from `WEBHOOK_SECRETS_FILE` or `WEBHOOK_SECRETS_DIR`,
+    /// falling back to environment variables when neither is set.
+    pub fn from_env() -> Result<Self, AppError> {
+        if let Ok(path) = std::env::var("WEBHOOK_SECRETS_FILE") {
+            return Self::from_file(PathBuf::from(path));
+        }
+        if let Ok(path) = std::env::var("WEBHOOK_SECRETS_DIR") {
+            return Self::from_directory(PathBuf::from(path));
+        }
+        Ok(Self::from_env_vars())
+    }
+
+    /// Whether secrets come from a file or directory rather than only from
+    /// environment variables.
+    pub fn is_configured(&self) -> bool {
+        self.location.is_some()
+    }
+
+    /// Re-reads the secret file or directory. On failure the previously
+    /// loaded secrets stay in effect.
+    pub fn reload(&self) -> Result<(), AppError> {
+        let Some(location) = &self.location else {
+            return Ok(());
+        };
+
+        let table = read_table(location)?;
+        *self.table.write().unwrap() = table;
+        info!("Reloaded webhook secrets from {:?}", location);
+        Ok(())
+    }
+
+    /// Reloads the secrets on `SIGHUP` and, when `WEBHOOK_SECRETS_RELOAD_SECS`
+    /// is set, on that interval, so rotated secrets take effect without a
+    /// restart.
+    pub async fn watch(resolver: Arc<Self>) -> Result<(), AppError> {
+        let interval = match std::env::var("WEBHOOK_SECRETS_RELOAD_SECS") {
+            Ok(secs) => Some(Duration::from_secs(secs.parse().map_err(|_| {
+                AppError::ConfigError(format!("Invalid WEBHOOK_SECRETS_RELOAD_SECS: {}", secs))
+            })?)),
+            Err(_) => None,
+        };
+
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+
+        loop {
+            match interval {
+                Some(interval) => {
+                    tokio::select! {
+                        _ = hangup.recv() => {}
+                        _ = tokio::time::sleep(interval) => {}
+                    }
+                }
+                None => {
+                    hangup.recv().await;
+                }
+            }
+
+            if let Err(e) = resolver.reload() {
+                warn!("Failed to reload webhook secrets, keeping previous set: {}", e);
+            }
+        }
+    }
+
+    /// Secrets currently accepted for `repository` deliveries from `source`.
+    pub fn secrets_for(&self, source: WebhookSource, repository: Option<&str>) -> Vec<String> {
+        let table = self.table.read().unwrap();
+
+        if let Some(secrets) = table.get(source.as_str()) {
+            if let Some(repo_secrets) = repository.and_then(|r| secrets.repositories.get(r)) {
+                return repo_secrets.clone();
+            }
+            if !secrets.default.is_empty() {
+                return secrets.default.clone();
+            }
+        }
+
+        std::env::var(source.secret_env_var()).into_iter().collect()
+    }
+
+    fn load(location: SecretLocation) -> Result<Self, AppError> {
+        let table = read_table(&location)?;
+        Ok(Self {
+            location: Some(location),
+            table: RwLock::new(table),
+        })
+    }
+}
+
+fn read_table(location: &SecretLocation) -> Result<SecretTable, AppError> {
+    match location {
+        SecretLocation::File(path) => {
+            let contents = std::fs::read(path)?;
+            serde_yaml::from_slice(&contents)
+                .map_err(|e| AppError::ConfigError(format!("Invalid secrets file {:?}: {}", path, e)))
+        }
+        SecretLocation::Directory(path) => read_directory(path),
+    }
+}
+
+fn read_directory(dir: &Path) -> Result<SecretTable, AppError> {
+    let mut table = SecretTable::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        // Skip hidden entries such as Kubernetes' `..data` symlinks.
+        if name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+
+        let secrets: Vec<String> = std::fs::read_to_string(&path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let Some((source, repository)) = parse_file_name(name) else {
+            warn!("Skipping secret file {:?} with an invalid repository name", path);
+            continue;
+        };
+
+        let entry = table.entry(source.to_lowercase()).or_default();
+        match repository {
+            Some(repository) => {
+                entry.repositories.insert(repository, secrets);
+            }
+            None => entry.default = secrets,
+        }
+    }
+
+    Ok(table)
+}
+
+/// Splits a secret directory file name into its source and, when it names
+/// one, the repository, or `None` when the repository is malformed.
+fn parse_file_name(name: &str) -> Option<(&str, Option<String>)> {
+    let Some((source, encoded)) = name.split_once(PATH_SEPARATOR) else {
+        return Some((name, None));
+    };
+
+    let mut repository = String::with_capacity(encoded.len());
+    let mut chars = encoded.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            PATH_SEPARATOR => repository.push('/'),
+            ESCAPE => match chars.next() {
+                Some(escaped @ (PATH_SEPARATOR | ESCAPE)) => repository.push(escaped),
+                _ => return None,
+            },
+            c => repository.push(c),
+        }
+    }
+
+    let valid = !repository.is_empty() && repository.split('/').all(|segment| !segment.is_empty());
+    valid.then_some((source, Some(repository)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_file_names() {
+        assert_eq!(parse_file_name("github"), Some(("github", None)));
+        assert_eq!(
+            parse_file_name("github.acme.widgets"),
+            Some(("github", Some("acme/widgets".to_string())))
+        );
+        assert_eq!(
+            parse_file_name("gitlab.acme.platform.my__service_.v2"),
+            Some(("gitlab", Some("acme/platform/my_service.v2".to_string())))
+        );
+        assert_eq!(
+            parse_file_name("github.acme._.github"),
+            Some(("github", Some("acme/.github".to_string())))
+        );
+
+        for malformed in ["github.", "github.acme..widgets", "github.acme.widgets_", "github.acme_widgets"] {
+            assert_eq!(parse_file_name(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn reloads_a_secret_directory() {
+        let dir = std::env::temp_dir().join(format!("webhook-secrets-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("github"), "org-secret\n").unwrap();
+        std::fs::write(dir.join("github.acme.widgets_.js"), "old-secret\n").unwrap();
+
+        let resolver = SecretResolver::from_directory(dir.clone()).unwrap();
+        assert_eq!(resolver.secrets_for(WebhookSource::GitHub, Some("acme/widgets.js")), ["old-secret"]);
+        assert_eq!(resolver.secrets_for(WebhookSource::GitHub, Some("acme/other")), ["org-secret"]);
+
+        std::fs::write(dir.join("github.acme.widgets_.js"), "old-secret\nnew-secret\n").unwrap();
+        std::fs::write(dir.join("github.acme_widgets"), "ignored\n").unwrap();
+        resolver.reload().unwrap();
+        assert_eq!(
+            resolver.secrets_for(WebhookSource::GitHub, Some("acme/widgets.js")),
+            ["old-secret", "new-secret"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
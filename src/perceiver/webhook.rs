@@ -1,47 +1,529 @@
 use axum::http::HeaderMap;
 use crate::errors::AppError;
-use crate::perceiver::event::NormalizedEvent;
+use crate::perceiver::archive::{ArchivedDelivery, DeliveryArchive};
+use crate::perceiver::dedup::DeliveryDeduplicator;
+use crate::perceiver::event::{NormalizedEvent, Platform};
+use crate::perceiver::secrets::SecretResolver;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
 
+/// Headers carrying shared secrets, never written to the delivery archive.
+const REDACTED_HEADERS: &[&str] = &["x-gitlab-token", "x-jenkins-token", "authorization"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WebhookSource {
     GitHub,
     GitLab,
+    Bitbucket,
+    Gitea,
+    Jenkins,
 }
 
-pub struct WebhookProcessor;
+impl WebhookSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookSource::GitHub => "github",
+            WebhookSource::GitLab => "gitlab",
+            WebhookSource::Bitbucket => "bitbucket",
+            WebhookSource::Gitea => "gitea",
+            WebhookSource::Jenkins => "jenkins",
+        }
+    }
+
+    /// Headers carrying the platform's unique delivery ID, in preference order.
+    fn delivery_id_headers(&self) -> &'static [&'static str] {
+        match self {
+            WebhookSource::GitHub => &["X-GitHub-Delivery"],
+            WebhookSource::GitLab => &["X-Gitlab-Event-UUID"],
+            WebhookSource::Bitbucket => &["X-Request-UUID"],
+            WebhookSource::Gitea => &["X-Gitea-Delivery", "X-Forgejo-Delivery"],
+            WebhookSource::Jenkins => &[],
+        }
+    }
+
+    /// Environment variable holding the secret used when no resolver entry
+    /// covers a delivery.
+    pub fn secret_env_var(&self) -> &'static str {
+        match self {
+            WebhookSource::GitHub => "GITHUB_WEBHOOK_SECRET",
+            WebhookSource::GitLab => "GITLAB_WEBHOOK_TOKEN",
+            WebhookSource::Bitbucket => "BITBUCKET_WEBHOOK_SECRET",
+            WebhookSource::Gitea => "GITEA_WEBHOOK_SECRET",
+            WebhookSource::Jenkins => "JENKINS_WEBHOOK_TOKEN",
+        }
+    }
+}
+
+pub struct WebhookProcessor {
+    dedup: DeliveryDeduplicator,
+    archive: Option<Arc<DeliveryArchive>>,
+    secrets: Arc<SecretResolver>,
+    verify_signatures: bool,
+}
 
 impl WebhookProcessor {
+    pub fn new(dedup: DeliveryDeduplicator) -> Self {
+        Self {
+            dedup,
+            archive: None,
+            secrets: Arc::new(SecretResolver::from_env_vars()),
+            verify_signatures: true,
+        }
+    }
+
+    /// Records every delivery that reaches verification in `archive`.
+    pub fn with_archive(mut self, archive: DeliveryArchive) -> Self {
+        self.archive = Some(Arc::new(archive));
+        self
+    }
+
+    /// Resolves per-repository secrets through `secrets` instead of only the
+    /// per-source environment variables.
+    pub fn with_secrets(mut self, secrets: Arc<SecretResolver>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Disables signature and token checks, for replaying recorded
+    /// deliveries offline. Never use this for a live endpoint.
+    pub fn without_verification(mut self) -> Self {
+        self.verify_signatures = false;
+        self
+    }
+
+    pub fn archive(&self) -> Option<&Arc<DeliveryArchive>> {
+        self.archive.as_ref()
+    }
+
+    /// Verifies and parses a GitHub delivery.
+    ///
+    /// Returns `Ok(None)` when the delivery is acknowledged but produces no
+    /// event (e.g. `ping` or a redelivery that was already processed).
     pub async fn process_github(
+        &self,
         headers: &HeaderMap,
         body: &[u8],
-    ) -> Result<NormalizedEvent, AppError> {
-        let signature = headers
-            .get("X-Hub-Signature-256")
+    ) -> Result<Option<NormalizedEvent>, AppError> {
+        let source = WebhookSource::GitHub;
+
+        let event_name = headers
+            .get("X-GitHub-Event")
             .and_then(|v| v.to_str().ok())
-            .ok_or(AppError::Unauthorized)?;
+            .ok_or_else(|| AppError::BadRequest("Missing X-GitHub-Event header".into()))?;
 
-        let secret = std::env::var("GITHUB_WEBHOOK_SECRET")
-            .map_err(|_| AppError::ConfigError("Missing GITHUB_WEBHOOK_SECRET".into()))?;
+        self.authenticate(source, headers, None, body)?;
 
-        crate::perceiver::github::verifier::verify_signature(body, signature, &secret)?;
-        crate::perceiver::github::parser::parse_payload(body)
+        let event = crate::perceiver::github::parser::parse_event(event_name, body);
+        self.record(source, headers, body, event.as_ref().map(|e| e.as_slice()));
+        let event = event?;
+
+        if event.is_some() && self.is_duplicate(source, headers) {
+            return Ok(None);
+        }
+
+        Ok(event)
     }
 
+    /// Verifies and parses a GitLab delivery.
+    ///
+    /// A Pipeline Hook produces the pipeline event plus one event per build.
+    /// Redeliveries that were already processed produce no events, and job
+    /// events already produced by a Job Hook or an earlier Pipeline Hook are
+    /// dropped.
     pub async fn process_gitlab(
+        &self,
         headers: &HeaderMap,
         body: &[u8],
-    ) -> Result<NormalizedEvent, AppError> {
-        let token = headers
-            .get("X-Gitlab-Token")
+    ) -> Result<Vec<NormalizedEvent>, AppError> {
+        let source = WebhookSource::GitLab;
+
+        self.authenticate(source, headers, None, body)?;
+
+        let event_name = headers
+            .get("X-Gitlab-Event")
+            .and_then(|v| v.to_str().ok());
+
+        let events = crate::perceiver::gitlab::parser::parse_event(event_name, body);
+        self.record(source, headers, body, events.as_deref());
+        let events = events?;
+
+        if self.is_duplicate(source, headers) {
+            return Ok(Vec::new());
+        }
+
+        Ok(events
+            .into_iter()
+            .filter(|event| match job_key(event) {
+                Some(key) => self.dedup.check_and_record(&key),
+                None => true,
+            })
+            .collect())
+    }
+
+    /// Verifies and parses a Bitbucket Cloud delivery. Returns `Ok(None)` for
+    /// redeliveries that were already processed.
+    ///
+    /// The steps of a finished pipeline are looked up later, by the agent.
+    pub async fn process_bitbucket(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<NormalizedEvent>, AppError> {
+        let source = WebhookSource::Bitbucket;
+
+        let event_key = headers
+            .get("X-Event-Key")
             .and_then(|v| v.to_str().ok())
-            .ok_or(AppError::Unauthorized)?;
+            .ok_or_else(|| AppError::BadRequest("Missing X-Event-Key header".into()))?;
+
+        self.authenticate(source, headers, None, body)?;
+
+        let event = crate::perceiver::bitbucket::parser::parse_event(event_key, body);
+        self.record(source, headers, body, event.as_ref().map(std::slice::from_ref));
+        let event = event?;
+
+        if self.is_duplicate(source, headers) {
+            return Ok(None);
+        }
+
+        Ok(Some(event))
+    }
+
+    /// Verifies and parses a Gitea / Forgejo delivery.
+    ///
+    /// Forgejo sends both its own `X-Forgejo-*` headers and the Gitea ones;
+    /// either set is accepted. Returns `Ok(None)` for redeliveries that were
+    /// already processed.
+    pub async fn process_gitea(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<NormalizedEvent>, AppError> {
+        let source = WebhookSource::Gitea;
+
+        let event_name = first_header(headers, &["X-Gitea-Event", "X-Forgejo-Event"])
+            .ok_or_else(|| AppError::BadRequest("Missing X-Gitea-Event header".into()))?;
+
+        self.authenticate(source, headers, None, body)?;
+
+        let event = crate::perceiver::gitea::parser::parse_event(event_name, body);
+        self.record(source, headers, body, event.as_ref().map(std::slice::from_ref));
+        let event = event?;
+
+        if self.is_duplicate(source, headers) {
+            return Ok(None);
+        }
+
+        Ok(Some(event))
+    }
+
+    /// Authenticates and parses a Jenkins build notification.
+    ///
+    /// The shared token is read from the `X-Jenkins-Token` header, falling
+    /// back to `query_token` since the Notification Plugin can only be
+    /// configured with a URL.
+    pub async fn process_jenkins(
+        &self,
+        headers: &HeaderMap,
+        query_token: Option<&str>,
+        body: &[u8],
+    ) -> Result<NormalizedEvent, AppError> {
+        let source = WebhookSource::Jenkins;
+
+        self.authenticate(source, headers, query_token, body)?;
+
+        let event = crate::perceiver::jenkins::parser::parse_payload(body);
+        self.record(source, headers, body, event.as_ref().map(std::slice::from_ref));
+        event
+    }
+
+    /// Checks the delivery's signature or shared token for `source`, unless
+    /// verification is disabled. Rejected deliveries are archived too.
+    fn authenticate(
+        &self,
+        source: WebhookSource,
+        headers: &HeaderMap,
+        query_token: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), AppError> {
+        if !self.verify_signatures {
+            return Ok(());
+        }
+
+        // The repository is read before the body is trusted; a forged name
+        // only selects which secrets the signature must match.
+        let repository = repository_of(source, body);
+        let secrets = self.secrets.secrets_for(source, repository.as_deref());
 
-        let expected_token = std::env::var("GITLAB_WEBHOOK_TOKEN")
-            .map_err(|_| AppError::ConfigError("Missing GITLAB_WEBHOOK_TOKEN".into()))?;
+        let result = if secrets.is_empty() {
+            Err(AppError::ConfigError(format!(
+                "No {} webhook secret configured for {}",
+                source.as_str(),
+                repository.as_deref().unwrap_or("unknown repository")
+            )))
+        } else {
+            match source {
+                WebhookSource::GitHub => verify_github(headers, body, &secrets),
+                WebhookSource::GitLab => verify_gitlab(headers, &secrets),
+                WebhookSource::Bitbucket => verify_bitbucket(headers, body, &secrets),
+                WebhookSource::Gitea => verify_gitea(headers, body, &secrets),
+                WebhookSource::Jenkins => verify_jenkins(headers, query_token, &secrets),
+            }
+        };
 
-        if token != expected_token {
-            return Err(AppError::Unauthorized);
+        if let Err(e) = &result {
+            self.archive_delivery(source, headers, body, format!("failed: {}", e), Err(e));
         }
 
-        crate::perceiver::gitlab::parser::parse_payload(body)
+        result
+    }
+
+    /// Archives a delivery that passed authentication, with the events (or
+    /// parse error) it produced.
+    fn record(
+        &self,
+        source: WebhookSource,
+        headers: &HeaderMap,
+        body: &[u8],
+        events: Result<&[NormalizedEvent], &AppError>,
+    ) {
+        let verification = if self.verify_signatures { "verified" } else { "skipped" };
+        self.archive_delivery(source, headers, body, verification.to_string(), events);
+    }
+
+    fn archive_delivery(
+        &self,
+        source: WebhookSource,
+        headers: &HeaderMap,
+        body: &[u8],
+        verification: String,
+        events: Result<&[NormalizedEvent], &AppError>,
+    ) {
+        let Some(archive) = &self.archive else {
+            return;
+        };
+
+        let delivery = ArchivedDelivery {
+            delivery_id: first_header(headers, source.delivery_id_headers()).map(str::to_string),
+            source: source.as_str().to_string(),
+            received_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    if REDACTED_HEADERS.contains(&name.as_str()) {
+                        return Some((name.to_string(), "[redacted]".to_string()));
+                    }
+                    value.to_str().ok().map(|v| (name.to_string(), v.to_string()))
+                })
+                .collect(),
+            body: String::from_utf8_lossy(body).into_owned(),
+            verification,
+            error: events.err().map(|e| e.to_string()),
+            events: events.map(<[NormalizedEvent]>::to_vec).unwrap_or_default(),
+        };
+
+        archive.record(delivery);
+    }
+
+    /// Records the delivery ID header for `source`, returning `true` if the
+    /// same delivery has already been accepted. Deliveries without an ID are
+    /// never treated as duplicates.
+    fn is_duplicate(&self, source: WebhookSource, headers: &HeaderMap) -> bool {
+        let Some(delivery_id) = first_header(headers, source.delivery_id_headers()) else {
+            return false;
+        };
+
+        if self.dedup.check_and_record(&format!("{}:{}", source.as_str(), delivery_id)) {
+            false
+        } else {
+            info!("Ignoring duplicate {} delivery {}", source.as_str(), delivery_id);
+            true
+        }
     }
-}
\ No newline at end of file
+
+    /// Forgets that a delivery and the GitLab jobs in `job_keys` were seen,
+    /// so the platform's retry of a delivery that could not be queued is not
+    /// dropped as a duplicate.
+    pub fn forget_delivery(&self, source: WebhookSource, headers: &HeaderMap, job_keys: &[String]) {
+        if let Some(delivery_id) = first_header(headers, source.delivery_id_headers()) {
+            self.dedup.forget(&format!("{}:{}", source.as_str(), delivery_id));
+        }
+        for key in job_keys {
+            self.dedup.forget(key);
+        }
+    }
+}
+
+/// Key identifying a GitLab job in one status. Job Hooks and the builds a
+/// Pipeline Hook lists report the same job status changes, under different
+/// delivery IDs.
+pub fn job_key(event: &NormalizedEvent) -> Option<String> {
+    if event.platform != Platform::GitLab {
+        return None;
+    }
+    Some(format!(
+        "gitlab-job:{}:{}:{}:{}",
+        event.instance_url.as_deref().unwrap_or_default(),
+        event.pipeline_id,
+        event.job_id.as_deref()?,
+        event.metadata.get("status")?
+    ))
+}
+
+/// Repository or project the delivery claims to come from, used to pick its
+/// secrets. Jenkins notifications are keyed by job name.
+fn repository_of(source: WebhookSource, body: &[u8]) -> Option<String> {
+    let payload: serde_json::Value = serde_json::from_slice(body).ok()?;
+
+    let name = match source {
+        WebhookSource::GitHub | WebhookSource::Bitbucket | WebhookSource::Gitea => {
+            payload.pointer("/repository/full_name")
+        }
+        WebhookSource::GitLab => payload.pointer("/project/path_with_namespace"),
+        WebhookSource::Jenkins => payload.get("name"),
+    };
+
+    name.and_then(|v| v.as_str()).map(str::to_string)
+}
+
+/// Accepts the delivery if `verify` succeeds for any currently valid secret.
+fn verify_any(secrets: &[String], verify: impl Fn(&str) -> Result<(), AppError>) -> Result<(), AppError> {
+    if secrets.iter().any(|secret| verify(secret).is_ok()) {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized)
+    }
+}
+
+fn verify_github(headers: &HeaderMap, body: &[u8], secrets: &[String]) -> Result<(), AppError> {
+    let signature = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+
+    verify_any(secrets, |secret| {
+        crate::perceiver::github::verifier::verify_signature(body, signature, secret)
+    })
+}
+
+fn verify_gitlab(headers: &HeaderMap, secrets: &[String]) -> Result<(), AppError> {
+    let token = headers
+        .get("X-Gitlab-Token")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+
+    verify_any(secrets, |expected_token| {
+        crate::perceiver::gitlab::verifier::verify_token(token, expected_token)
+    })
+}
+
+fn verify_bitbucket(headers: &HeaderMap, body: &[u8], secrets: &[String]) -> Result<(), AppError> {
+    let signature = headers
+        .get("X-Hub-Signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+
+    verify_any(secrets, |secret| {
+        crate::perceiver::bitbucket::verifier::verify_signature(body, signature, secret)
+    })
+}
+
+fn verify_gitea(headers: &HeaderMap, body: &[u8], secrets: &[String]) -> Result<(), AppError> {
+    let signature = first_header(headers, &["X-Gitea-Signature", "X-Forgejo-Signature"])
+        .ok_or(AppError::Unauthorized)?;
+
+    verify_any(secrets, |secret| {
+        crate::perceiver::gitea::verifier::verify_signature(body, signature, secret)
+    })
+}
+
+fn verify_jenkins(headers: &HeaderMap, query_token: Option<&str>, secrets: &[String]) -> Result<(), AppError> {
+    let token = headers
+        .get("X-Jenkins-Token")
+        .and_then(|v| v.to_str().ok())
+        .or(query_token)
+        .ok_or(AppError::Unauthorized)?;
+
+    verify_any(secrets, |expected_token| {
+        crate::perceiver::jenkins::verifier::verify_token(token, expected_token)
+    })
+}
+
+fn first_header<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name).and_then(|v| v.to_str().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn gitlab_headers(event: &str, delivery_id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Gitlab-Event", event.parse().unwrap());
+        headers.insert("X-Gitlab-Event-UUID", delivery_id.parse().unwrap());
+        headers
+    }
+
+    fn job_ids(events: &[NormalizedEvent]) -> Vec<Option<&str>> {
+        events.iter().map(|event| event.job_id.as_deref()).collect()
+    }
+
+    #[tokio::test]
+    async fn drops_gitlab_jobs_already_reported() {
+        let processor = WebhookProcessor::new(DeliveryDeduplicator::new(100, Duration::from_secs(60)))
+            .without_verification();
+        let job_hook = include_bytes!("gitlab/fixtures/job_hook.json");
+        let pipeline_hook = include_bytes!("gitlab/fixtures/pipeline_hook.json");
+
+        let events = processor
+            .process_gitlab(&gitlab_headers("Job Hook", "job-380"), job_hook)
+            .await
+            .unwrap();
+        assert_eq!(job_ids(&events), [Some("380")]);
+
+        // Build 380 failed as the Job Hook already said; the pipeline event
+        // is always kept.
+        let events = processor
+            .process_gitlab(&gitlab_headers("Pipeline Hook", "pipeline-123"), pipeline_hook)
+            .await
+            .unwrap();
+        assert_eq!(job_ids(&events), [None, Some("379"), Some("381"), Some("382")]);
+        let keys: Vec<_> = events.iter().filter_map(job_key).collect();
+        assert_eq!(keys.len(), 3);
+
+        // Once the delivery and its jobs are forgotten, the retry goes through.
+        let headers = gitlab_headers("Pipeline Hook", "pipeline-123");
+        processor.forget_delivery(WebhookSource::GitLab, &headers, &keys);
+        let events = processor.process_gitlab(&headers, pipeline_hook).await.unwrap();
+        assert_eq!(job_ids(&events), [None, Some("379"), Some("381"), Some("382")]);
+    }
+
+    #[test]
+    fn keys_gitlab_jobs_by_status() {
+        let mut event = NormalizedEvent::new(
+            Platform::GitLab,
+            "123".to_string(),
+            Some("380".to_string()),
+            crate::perceiver::event::EventType::JobStarted,
+            None,
+        );
+        event.instance_url = Some("https://gitlab.example.com".to_string());
+        assert_eq!(job_key(&event), None);
+
+        event.metadata.insert("status".to_string(), "running".to_string());
+        assert_eq!(
+            job_key(&event).as_deref(),
+            Some("gitlab-job:https://gitlab.example.com:123:380:running")
+        );
+
+        event.platform = Platform::GitHub;
+        assert_eq!(job_key(&event), None);
+    }
+}
@@ -2,8 +2,11 @@ use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ActionPlan {
-    RetryJob { job_id: String },
-    CommentOnPR { message: String },
+    /// Re-run job `job_id`, unless its work has already been run
+    /// `max_attempts` times.
+    RetryJob { job_id: String, max_attempts: u32 },
+    /// Comment on pull request (or GitLab merge request) `pr_number`.
+    CommentOnPR { pr_number: Option<u64>, message: String },
     SendEmail { subject: String, body: String },
     SendSlack { channel: String, message: String },
 }
\ No newline at end of file
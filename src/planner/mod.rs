@@ -14,20 +14,28 @@ pub async fn plan_actions(
 ) -> Result<Vec<ActionPlan>, AppError> {
     let mut actions = Vec::new();
 
+    // A job that was cancelled, skipped or held for approval stopped on
+    // purpose; retrying it would override that decision. A job that keeps
+    // failing is only retried until it reaches its last attempt.
+    let retryable = !event.event_type.is_intentional_stop()
+        && event.attempt.unwrap_or(1) < config.max_retry_attempts;
+
     for diagnosis in diagnoses {
         match diagnosis {
             Diagnosis::FlakyTest { test_name, reason } => {
-                if config.allow_flaky_retry {
+                if config.allow_flaky_retry && retryable {
                     if let Some(job_id) = &event.job_id {
                         actions.push(ActionPlan::RetryJob {
                             job_id: job_id.clone(),
+                            max_attempts: config.max_retry_attempts,
                         });
                     }
                 }
 
-                let repo = event.metadata.get("repository").unwrap_or(&"<repo>".to_string());
+                let repo = event.repository.as_deref().unwrap_or("<repo>");
 
                 actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
                     message: format!(
                         "⚠️ Flaky test detected: `{}`\nReason: {}\nRepo: {}",
                         test_name, reason, repo
@@ -37,6 +45,7 @@ pub async fn plan_actions(
 
             Diagnosis::LongRuntime { job_name, duration } => {
                 actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
                     message: format!(
                         "⏱️ Job `{}` took too long ({}s). Consider caching or splitting steps.",
                         job_name, duration
@@ -44,8 +53,34 @@ pub async fn plan_actions(
                 });
             }
 
+            Diagnosis::LongStepRuntime { job_name, step, duration } => {
+                actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
+                    message: format!(
+                        "⏱️ Step `{}` of job `{}` took too long ({}s). Consider caching or splitting it.",
+                        step, job_name, duration
+                    ),
+                });
+            }
+
+            Diagnosis::StepFailure { step, message, location, context } => {
+                let step = step.as_deref().map(|step| format!(" in step `{}`", step)).unwrap_or_default();
+                let location = location.as_deref().map(|location| format!(" at `{}`", location)).unwrap_or_default();
+                let context = if context.is_empty() {
+                    String::new()
+                } else {
+                    format!("\n\n```\n{}\n```", context.join("\n"))
+                };
+
+                actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
+                    message: format!("❌ Job failed{}{}: {}{}", step, location, message, context),
+                });
+            }
+
             Diagnosis::InefficientJobOrder { recommendation } => {
                 actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
                     message: format!(
                         "🔀 Job ordering could be improved: {}",
                         recommendation
@@ -55,6 +90,7 @@ pub async fn plan_actions(
 
             Diagnosis::CacheMiss { step } => {
                 actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
                     message: format!(
                         "📦 Cache miss detected at step: `{}`. Consider persistent caching.",
                         step
@@ -64,6 +100,7 @@ pub async fn plan_actions(
 
             Diagnosis::ConfigurationViolation { description } => {
                 actions.push(ActionPlan::CommentOnPR {
+                    pr_number: event.pr_number,
                     message: format!(
                         "🚨 Config violation: {}",
                         description
@@ -72,14 +109,22 @@ pub async fn plan_actions(
             }
 
             Diagnosis::InfraFailure => {
-                if let Some(job_id) = &event.job_id {
+                let retry_job_id = event.job_id.as_ref().filter(|_| retryable);
+                if let Some(job_id) = retry_job_id {
                     actions.push(ActionPlan::RetryJob {
                         job_id: job_id.clone(),
+                        max_attempts: config.max_retry_attempts,
                     });
                 }
 
+                let message = if retry_job_id.is_some() {
+                    "⚙️ Infrastructure failure detected. Retrying job..."
+                } else {
+                    "⚙️ Infrastructure failure detected."
+                };
                 actions.push(ActionPlan::CommentOnPR {
-                    message: "⚙️ Infrastructure failure detected. Retrying job...".to_string(),
+                    pr_number: event.pr_number,
+                    message: message.to_string(),
                 });
             }
         }
@@ -87,3 +132,43 @@ pub async fn plan_actions(
 
     Ok(actions)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perceiver::event::{EventType, Platform};
+
+    fn failed_job(attempt: Option<u32>) -> NormalizedEvent {
+        let mut event = NormalizedEvent::new(
+            Platform::GitHub,
+            "42".to_string(),
+            Some("7".to_string()),
+            EventType::JobFailed,
+            None,
+        );
+        event.attempt = attempt;
+        event
+    }
+
+    fn retries(actions: &[ActionPlan]) -> usize {
+        actions
+            .iter()
+            .filter(|action| matches!(action, ActionPlan::RetryJob { .. }))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn stops_retrying_at_the_last_attempt() {
+        let config = Config {
+            max_retry_attempts: 2,
+            ..Config::default()
+        };
+
+        for (attempt, expected) in [(None, 1), (Some(1), 1), (Some(2), 0), (Some(3), 0)] {
+            let actions = plan_actions(&failed_job(attempt), &[Diagnosis::InfraFailure], &config)
+                .await
+                .unwrap();
+            assert_eq!(retries(&actions), expected, "attempt {:?}", attempt);
+        }
+    }
+}
@@ -1,43 +1,114 @@
-use tokio::sync::mpsc;
-use tracing::{info, error};
+use std::sync::Arc;
+use tracing::{info, error, warn};
 use crate::analyzer;
 use crate::config;
 use crate::planner;
 use crate::actuator;
-use crate::perceiver::event::NormalizedEvent;
+use crate::actuator::retries::RetryLedger;
+use crate::analyzer::diagnosis::Diagnosis;
+use crate::perceiver::bitbucket;
+use crate::perceiver::event::{EventType, NormalizedEvent};
+use crate::perceiver::queue::EventQueue;
+use crate::perceiver::review::ReviewResolver;
+use crate::planner::action_plan::ActionPlan;
 
 pub struct Agent {
-    receiver: mpsc::Receiver<NormalizedEvent>,
+    queue: Arc<EventQueue>,
+    reviews: ReviewResolver,
+    retries: RetryLedger,
 }
 
 impl Agent {
-    pub fn new(receiver: mpsc::Receiver<NormalizedEvent>) -> Self {
-        Self { receiver }
+    pub fn new(queue: Arc<EventQueue>, reviews: ReviewResolver) -> Self {
+        Self {
+            queue,
+            reviews,
+            retries: RetryLedger::new(),
+        }
     }
 
     pub async fn run(&mut self) {
-        while let Some(event) = self.receiver.recv().await {
-            if let Err(e) = self.process_event(event).await {
+        loop {
+            let queued = self.queue.next().await;
+            if let Err(e) = self.process_event(queued.event).await {
                 error!("Error processing event: {}", e);
             }
+
+            // Failed events are acknowledged too: retrying the same event
+            // would most likely fail the same way and block the queue.
+            if let Err(e) = self.queue.ack(queued.seq).await {
+                error!("Failed to acknowledge event {}: {}", queued.seq, e);
+            }
         }
     }
 
-    async fn process_event(&self, event: NormalizedEvent) -> anyhow::Result<()> {
+    async fn process_event(&self, mut event: NormalizedEvent) -> anyhow::Result<()> {
+        if event.event_type != EventType::Unknown {
+            self.reviews.resolve(&mut event).await;
+        }
+
+        let mut events = Self::expand(vec![event]).await.into_iter();
+        let event = events.next().expect("expansion keeps the event");
+        let result = self.handle(&event).await;
+
+        // One step failing to process must not hold back the others.
+        for step in events {
+            if let Err(e) = self.handle(&step).await {
+                error!("Error processing event {}: {}", step.platform_id, e);
+            }
+        }
+
+        result
+    }
+
+    async fn handle(&self, event: &NormalizedEvent) -> anyhow::Result<()> {
         info!("Processing event: {:?}", event);
 
+        let (_, actions) = Self::evaluate(event).await?;
+
+        // Execute actions
+        actuator::execute_actions(event, &actions, &self.retries).await?;
+
+        Ok(())
+    }
+
+    /// `events`, each followed by the job events it stands for that no
+    /// webhook delivers on its own: the steps of a Bitbucket pipeline.
+    pub async fn expand(events: Vec<NormalizedEvent>) -> Vec<NormalizedEvent> {
+        let mut expanded = Vec::with_capacity(events.len());
+        for event in events {
+            let steps = bitbucket::step_events(&event).await.unwrap_or_else(|e| {
+                warn!("Failed to list the steps of {}: {}", event.platform_id, e);
+                Vec::new()
+            });
+            expanded.push(event);
+            expanded.extend(steps);
+        }
+        expanded
+    }
+
+    /// Runs the analyzer and planner for an event without executing any of
+    /// the planned actions.
+    pub async fn evaluate(
+        event: &NormalizedEvent,
+    ) -> anyhow::Result<(Vec<Diagnosis>, Vec<ActionPlan>)> {
+        if event.event_type == EventType::Unknown {
+            info!(
+                "Skipping unclassified {} event",
+                event.raw_event_type.as_deref().unwrap_or("unknown")
+            );
+            return Ok((Vec::new(), Vec::new()));
+        }
+
         // Load config for this repository
-        let config = config::loader::load_for_event(&event).await?;
+        let config = config::loader::load_for_event(event).await?;
 
         // Analyze event
-        let diagnoses = analyzer::analyze_event(&event, &config).await?;
+        let diagnoses = analyzer::analyze_event(event, &config).await?;
 
         // Plan actions
-        let actions = planner::plan_actions(&event, &diagnoses, &config).await?;
+        let actions = planner::plan_actions(event, &diagnoses, &config).await?;
 
-        // Execute actions
-        actuator::execute_actions(&event, &actions).await?;
-
-        Ok(())
+        Ok((diagnoses, actions))
     }
 }
\ No newline at end of file
@@ -1 +1,2 @@
-pub mod agent;
\ No newline at end of file
+pub mod agent;
+pub mod replay;
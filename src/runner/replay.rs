@@ -0,0 +1,157 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::errors::AppError;
+use crate::perceiver::event::NormalizedEvent;
+use crate::perceiver::webhook::WebhookProcessor;
+use crate::runner::agent::Agent;
+
+/// A captured webhook delivery.
+///
+/// `body` is the raw payload exactly as received, so signatures computed
+/// over it still verify; re-serializing a JSON payload would not preserve
+/// its bytes. When `source` is omitted it is inferred from the platform
+/// event header.
+#[derive(Debug, Deserialize)]
+pub struct RecordedDelivery {
+    pub source: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl RecordedDelivery {
+    fn body_bytes(&self) -> &[u8] {
+        self.body.as_bytes()
+    }
+
+    fn header_map(&self) -> Result<HeaderMap, AppError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| AppError::BadRequest(format!("Invalid header name {}: {}", name, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| AppError::BadRequest(format!("Invalid header value: {}", e)))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    fn source(&self, headers: &HeaderMap) -> Option<String> {
+        if let Some(source) = &self.source {
+            return Some(source.to_lowercase());
+        }
+
+        [
+            ("X-GitHub-Event", "github"),
+            ("X-Gitlab-Event", "gitlab"),
+            ("X-Event-Key", "bitbucket"),
+            ("X-Gitea-Event", "gitea"),
+            ("X-Forgejo-Event", "gitea"),
+            ("X-Jenkins-Token", "jenkins"),
+        ]
+        .iter()
+        .find(|(header, _)| headers.contains_key(*header))
+        .map(|(_, source)| source.to_string())
+    }
+}
+
+/// Loads deliveries from an NDJSON file (one delivery per line) or from a
+/// directory of `.json` / `.ndjson` files, in file name order.
+pub fn load_deliveries(path: &Path) -> Result<Vec<RecordedDelivery>, AppError> {
+    if !path.is_dir() {
+        return parse_file(path);
+    }
+
+    let mut files: Vec<_> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("json") | Some("ndjson")))
+        .collect();
+    files.sort();
+
+    let mut deliveries = Vec::new();
+    for file in files {
+        deliveries.extend(parse_file(&file)?);
+    }
+    Ok(deliveries)
+}
+
+fn parse_file(path: &Path) -> Result<Vec<RecordedDelivery>, AppError> {
+    let contents = std::fs::read_to_string(path)?;
+    let invalid = |e: serde_json::Error| AppError::BadRequest(format!("Invalid delivery in {:?}: {}", path, e));
+
+    // A `.json` file holds a single, possibly pretty-printed, delivery.
+    if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        return Ok(vec![serde_json::from_str(&contents).map_err(invalid)?]);
+    }
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(invalid))
+        .collect()
+}
+
+/// Pushes each delivery through the processor and the agent's analysis and
+/// planning stages, printing the diagnoses and planned actions. Actions are
+/// never executed.
+pub async fn replay(processor: &WebhookProcessor, deliveries: &[RecordedDelivery]) {
+    for (index, delivery) in deliveries.iter().enumerate() {
+        println!("[{}] delivery", index + 1);
+
+        let events = match process(processor, delivery).await {
+            Ok(events) => events,
+            Err(e) => {
+                println!("    rejected: {}", e);
+                continue;
+            }
+        };
+
+        if events.is_empty() {
+            println!("    acknowledged, no events");
+        }
+
+        for event in Agent::expand(events).await {
+            println!(
+                "    event {} {:?} ({})",
+                event.platform_id,
+                event.event_type,
+                event.raw_event_type.as_deref().unwrap_or("-")
+            );
+
+            match Agent::evaluate(&event).await {
+                Ok((diagnoses, actions)) => {
+                    for diagnosis in &diagnoses {
+                        println!("        diagnosis: {:?}", diagnosis);
+                    }
+                    for action in &actions {
+                        println!("        action: {:?}", action);
+                    }
+                    if diagnoses.is_empty() && actions.is_empty() {
+                        println!("        no diagnoses");
+                    }
+                }
+                Err(e) => println!("        evaluation failed: {}", e),
+            }
+        }
+    }
+}
+
+async fn process(
+    processor: &WebhookProcessor,
+    delivery: &RecordedDelivery,
+) -> Result<Vec<NormalizedEvent>, AppError> {
+    let headers = delivery.header_map()?;
+    let body = delivery.body_bytes();
+
+    match delivery.source(&headers).as_deref() {
+        Some("github") => Ok(processor.process_github(&headers, body).await?.into_iter().collect()),
+        Some("gitlab") => processor.process_gitlab(&headers, body).await,
+        Some("bitbucket") => Ok(processor.process_bitbucket(&headers, body).await?.into_iter().collect()),
+        Some("gitea") => Ok(processor.process_gitea(&headers, body).await?.into_iter().collect()),
+        Some("jenkins") => Ok(vec![processor.process_jenkins(&headers, None, body).await?]),
+        Some(other) => Err(AppError::BadRequest(format!("Unknown delivery source: {}", other))),
+        None => Err(AppError::BadRequest("Cannot infer delivery source".into())),
+    }
+}
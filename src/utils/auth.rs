@@ -0,0 +1,10 @@
+use crate::errors::AppError;
+
+/// Compares a shared token with the expected one in constant time.
+pub fn verify_token(token: &str, expected_token: &str) -> Result<(), AppError> {
+    if subtle::ConstantTimeEq::ct_eq(token.as_bytes(), expected_token.as_bytes()).into() {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized)
+    }
+}
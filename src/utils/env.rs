@@ -0,0 +1,11 @@
+use crate::errors::AppError;
+
+/// Parses environment variable `name`, falling back to `default` when unset.
+pub fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> Result<T, AppError> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| AppError::ConfigError(format!("Invalid {}: {}", name, value))),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,21 @@
+/// Whether `url` is `base_url` or a path below it. Unlike a plain prefix
+/// check, `https://ci.example.com` does not match
+/// `https://ci.example.com.attacker.net`.
+pub fn is_under(url: &str, base_url: &str) -> bool {
+    url.strip_prefix(base_url.trim_end_matches('/'))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_only_paths_below_the_base_url() {
+        assert!(is_under("https://ci.example.com", "https://ci.example.com"));
+        assert!(is_under("https://ci.example.com/job/1/", "https://ci.example.com/"));
+        assert!(!is_under("https://ci.example.com.attacker.net/job/1/", "https://ci.example.com"));
+        assert!(!is_under("https://ci.example.community/job/1/", "https://ci.example.com"));
+        assert!(!is_under("https://api.bitbucket.org/2.0x", "https://api.bitbucket.org/2.0"));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod auth;
+pub mod env;
+pub mod http;